@@ -28,6 +28,11 @@ pub fn build_app(name: &str) -> App {
                         .long("all")
                         .short("a")
                         .help("Show all sequences together with duplicates"),
+                ).arg(
+                    Arg::with_name("derivation")
+                        .long("derivation")
+                        .short("d")
+                        .help("Show the derivation of each sequence"),
                 ).arg(
                     Arg::with_name("chomsky")
                         .long("chomsky")
@@ -0,0 +1,478 @@
+use cfg::{Linearity, Nonterminal, Production, Symbol, Terminal, CFG};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+/// The grammar is not right-linear, so it can not be converted to a finite automaton
+#[derive(Debug, PartialEq)]
+pub struct NotRegular;
+
+impl fmt::Display for NotRegular {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The grammar is not right-linear")
+    }
+}
+
+/// Nondeterministic finite automaton, states are numbered from 0,
+/// transitions by None are epsilon transitions
+#[derive(Debug)]
+pub struct Nfa {
+    pub start: usize,
+    pub accept: HashSet<usize>,
+    states: usize,
+    jump: HashMap<(usize, Option<char>), BTreeSet<usize>>,
+}
+
+impl Nfa {
+    pub fn new() -> Nfa {
+        Nfa {
+            start: 0,
+            accept: HashSet::new(),
+            states: 1,
+            jump: HashMap::new(),
+        }
+    }
+
+    /// One state per Nonterminal plus the accepting state,
+    /// 'A -> wB' adds a path by the terminals of 'w' from A to B
+    /// and 'A -> w' adds the path from A to the accepting state
+    pub fn from_grammar(grammar: &CFG) -> Result<Nfa, NotRegular> {
+        if grammar.is_regular() != Some(Linearity::Right) {
+            return Err(NotRegular);
+        }
+        let mut nfa = Nfa::new();
+        let mut states: HashMap<Nonterminal, usize> = HashMap::new();
+        states.insert(grammar.start.clone(), nfa.start);
+        for nonterm in grammar.nonterminals() {
+            if !states.contains_key(&nonterm) {
+                let state = nfa.add_state();
+                states.insert(nonterm, state);
+            }
+        }
+        let accept = nfa.add_state();
+        nfa.accept.insert(accept);
+
+        for rule in &grammar.productions {
+            let (word, to) = match rule.right.last() {
+                Some(&Symbol::N(ref n)) => (&rule.right[..rule.right.len() - 1], states[n]),
+                _ => (&rule.right[..], accept),
+            };
+            let mut from = states[&rule.left];
+            if word.is_empty() {
+                nfa.add_transition(from, None, to);
+                continue;
+            }
+            for (idx, sym) in word.iter().enumerate() {
+                let next = if idx + 1 == word.len() {
+                    to
+                } else {
+                    nfa.add_state()
+                };
+                let symbol = match sym {
+                    &Symbol::T(ref t) => t.symbol,
+                    &Symbol::N(_) => unreachable!(), // checked by is_regular
+                };
+                nfa.add_transition(from, Some(symbol), next);
+                from = next;
+            }
+        }
+        Ok(nfa)
+    }
+
+    pub fn add_state(&mut self) -> usize {
+        self.states += 1;
+        self.states - 1
+    }
+
+    pub fn add_transition(&mut self, from: usize, symbol: Option<char>, to: usize) {
+        self.jump
+            .entry((from, symbol))
+            .or_insert_with(BTreeSet::new)
+            .insert(to);
+    }
+
+    pub fn states_count(&self) -> usize {
+        self.states
+    }
+
+    /// Accepts the reversed strings of self: all the transitions are turned around
+    /// and the new start state has epsilon transitions to the accepting states
+    pub fn reverse(&self) -> Nfa {
+        let mut nfa = Nfa {
+            start: self.states,
+            accept: HashSet::new(),
+            states: self.states + 1,
+            jump: HashMap::new(),
+        };
+        nfa.accept.insert(self.start);
+        for (&(from, symbol), states) in &self.jump {
+            for &to in states {
+                nfa.add_transition(to, symbol, from);
+            }
+        }
+        for &accept in &self.accept {
+            nfa.add_transition(nfa.start, None, accept);
+        }
+        nfa
+    }
+
+    fn epsilon_closure(&self, states: &mut BTreeSet<usize>) {
+        let mut stack: Vec<usize> = states.iter().cloned().collect();
+        while let Some(state) = stack.pop() {
+            if let Some(next) = self.jump.get(&(state, None)) {
+                for &s in next {
+                    if states.insert(s) {
+                        stack.push(s);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn simulate(&self, input: &str) -> bool {
+        let mut current = BTreeSet::new();
+        current.insert(self.start);
+        self.epsilon_closure(&mut current);
+        for ch in input.chars() {
+            let mut next = BTreeSet::new();
+            for &state in &current {
+                if let Some(states) = self.jump.get(&(state, Some(ch))) {
+                    next.extend(states.iter().cloned());
+                }
+            }
+            self.epsilon_closure(&mut next);
+            if next.is_empty() {
+                return false;
+            }
+            current = next;
+        }
+        current.iter().any(|x| self.accept.contains(x))
+    }
+
+    /// The subset construction, the empty subset is kept as the dead state,
+    /// so the DFA has a transition by every symbol of the alphabet from every state
+    pub fn to_dfa(&self, alphabet: &BTreeSet<char>) -> Dfa {
+        let mut start = BTreeSet::new();
+        start.insert(self.start);
+        self.epsilon_closure(&mut start);
+        let mut dfa = Dfa {
+            start: 0,
+            accept: HashSet::new(),
+            alphabet: alphabet.clone(),
+            states: 1,
+            jump: HashMap::new(),
+        };
+        let mut subsets: HashMap<BTreeSet<usize>, usize> = HashMap::new();
+        subsets.insert(start.clone(), dfa.start);
+        let mut stack = vec![start];
+        while let Some(subset) = stack.pop() {
+            let from = subsets[&subset];
+            if subset.iter().any(|x| self.accept.contains(x)) {
+                dfa.accept.insert(from);
+            }
+            for &symbol in alphabet {
+                let mut next = BTreeSet::new();
+                for &state in &subset {
+                    if let Some(states) = self.jump.get(&(state, Some(symbol))) {
+                        next.extend(states.iter().cloned());
+                    }
+                }
+                self.epsilon_closure(&mut next);
+                let to = match subsets.get(&next) {
+                    Some(&to) => to,
+                    None => {
+                        dfa.states += 1;
+                        stack.push(next.clone());
+                        dfa.states - 1
+                    }
+                };
+                subsets.insert(next, to);
+                dfa.jump.insert((from, symbol), to);
+            }
+        }
+        dfa
+    }
+}
+
+/// Complete deterministic finite automaton over the alphabet, states are numbered from 0
+#[derive(Debug)]
+pub struct Dfa {
+    pub start: usize,
+    pub accept: HashSet<usize>,
+    alphabet: BTreeSet<char>,
+    states: usize,
+    jump: HashMap<(usize, char), usize>,
+}
+
+impl Dfa {
+    pub fn states_count(&self) -> usize {
+        self.states
+    }
+
+    pub fn alphabet(&self) -> &BTreeSet<char> {
+        &self.alphabet
+    }
+
+    pub fn next(&self, state: usize, symbol: char) -> Option<usize> {
+        self.jump.get(&(state, symbol)).cloned()
+    }
+
+    pub fn accepts(&self, input: &str) -> bool {
+        let mut state = self.start;
+        for ch in input.chars() {
+            state = match self.next(state, ch) {
+                Some(next) => next,
+                None => return false,
+            };
+        }
+        self.accept.contains(&state)
+    }
+
+    /// The search for a reachable pair of states of the product where only one is accepting,
+    /// a missing transition (by a symbol out of the alphabet) leads to the dead state None
+    pub fn equivalent(&self, other: &Dfa) -> bool {
+        let alphabet: BTreeSet<char> = self.alphabet.union(&other.alphabet).cloned().collect();
+        let accepting =
+            |dfa: &Dfa, state: Option<usize>| state.map_or(false, |x| dfa.accept.contains(&x));
+        let start = (Some(self.start), Some(other.start));
+        let mut visited = HashSet::new();
+        visited.insert(start);
+        let mut stack = vec![start];
+        while let Some((left, right)) = stack.pop() {
+            if accepting(self, left) != accepting(other, right) {
+                return false;
+            }
+            for &symbol in &alphabet {
+                let next = (
+                    left.and_then(|x| self.next(x, symbol)),
+                    right.and_then(|x| other.next(x, symbol)),
+                );
+                if visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        true
+    }
+
+    /// Accepts the strings over the alphabet that are rejected by self
+    pub fn complement(&self) -> Dfa {
+        Dfa {
+            start: self.start,
+            accept: (0..self.states).filter(|x| !self.accept.contains(x)).collect(),
+            alphabet: self.alphabet.clone(),
+            states: self.states,
+            jump: self.jump.clone(),
+        }
+    }
+
+    /// Right-linear grammar with the Nonterminal 'Q', '<Q1>', ... for every state:
+    /// 'Qi -> aQj' for every transition and 'Qi -> ε' for every accepting state,
+    /// the states that do not lead to an accepting one are removed
+    pub fn to_grammar(&self) -> CFG {
+        let state = |x: usize| Nonterminal::new("Q".to_string(), x as u32);
+        let mut productions = BTreeSet::new();
+        for (&(from, symbol), &to) in &self.jump {
+            let right = vec![Symbol::T(Terminal::new(symbol)), Symbol::N(state(to))];
+            productions.insert(Production::new(state(from), right));
+        }
+        for &accept in &self.accept {
+            productions.insert(Production::new(state(accept), vec![]));
+        }
+        CFG::new(state(self.start), productions)
+            .remove_useless_rules()
+            .remove_unreachable_rules()
+    }
+}
+
+/// Single state pushdown automaton accepting by the empty stack
+#[derive(Debug)]
+pub struct Pda {
+    pub start: Symbol,
+    /// the input symbol (None for epsilon) and the top of the stack
+    /// to the sequences replacing the top of the stack
+    jump: HashMap<(Option<char>, Symbol), Vec<Vec<Symbol>>>,
+    nullable: HashSet<Nonterminal>,
+    max_depth: usize,
+}
+
+impl Pda {
+    /// The leftmost derivation simulation: a Nonterminal on the top of the stack is replaced
+    /// by the right side of its rule, a terminal on the top is matched with the input and popped.
+    /// The rules are taken from the simplified grammar, so only the start symbol is nullable
+    /// and every other symbol on the stack consumes at least one input symbol
+    pub fn from_grammar(grammar: &CFG) -> Pda {
+        let grammar = grammar.simplify();
+        let mut jump: HashMap<(Option<char>, Symbol), Vec<Vec<Symbol>>> = HashMap::new();
+        for rule in &grammar.productions {
+            jump.entry((None, Symbol::N(rule.left.clone())))
+                .or_insert_with(Vec::new)
+                .push(rule.right.iter().rev().cloned().collect());
+        }
+        for term in grammar.terminals() {
+            jump.insert((Some(term.symbol), Symbol::T(term)), vec![vec![]]);
+        }
+        Pda {
+            start: Symbol::N(grammar.start.clone()),
+            jump: jump,
+            nullable: grammar.nullable_symbols(),
+            max_depth: 1000,
+        }
+    }
+
+    /// The bound on the number of moves in one computation, 1000 by default
+    pub fn with_max_depth(mut self, depth: usize) -> Pda {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Searches for a computation that reads the input and empties the stack
+    pub fn accepts(&self, input: &str) -> bool {
+        let input: Vec<char> = input.chars().collect();
+        let mut visited: HashSet<(usize, Vec<Symbol>)> = HashSet::new();
+        let mut queue = vec![(0, vec![self.start.clone()], 0)];
+        while let Some((pos, stack, depth)) = queue.pop() {
+            let top = match stack.last() {
+                Some(top) => top.clone(),
+                None if pos == input.len() => return true,
+                None => continue,
+            };
+            // every symbol on the stack but the nullable start consumes the input
+            let required = stack
+                .iter()
+                .filter(|x| x.as_nonterminal().map_or(true, |n| !self.nullable.contains(n)))
+                .count();
+            if depth >= self.max_depth
+                || required > input.len() - pos
+                || !visited.insert((pos, stack.clone()))
+            {
+                continue;
+            }
+            let mut moves = Vec::new();
+            if let Some(replaces) = self.jump.get(&(None, top.clone())) {
+                moves.extend(replaces.iter().map(|x| (pos, x)));
+            }
+            if pos < input.len() {
+                if let Some(replaces) = self.jump.get(&(Some(input[pos]), top)) {
+                    moves.extend(replaces.iter().map(|x| (pos + 1, x)));
+                }
+            }
+            for (next_pos, replace) in moves {
+                let mut next = stack[..stack.len() - 1].to_vec();
+                next.extend(replace.iter().cloned());
+                queue.push((next_pos, next, depth + 1));
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use self::super::*;
+
+    #[test]
+    fn nfa_from_grammar() {
+        let test_rules = "
+            S -> aS | bA |
+            A -> abA | cS | c
+        ";
+        let nfa = CFG::load_from_str(test_rules).unwrap().to_nfa().unwrap();
+        for input in vec!["", "a", "aaa", "bc", "bababc", "bcbc", "abcaa"] {
+            assert!(nfa.simulate(input), "Reject {}", input);
+        }
+        for input in vec!["b", "ba", "bab", "c", "bcb", "abd"] {
+            assert!(!nfa.simulate(input), "Accept {}", input);
+        }
+
+        let nfa = CFG::load_from_str("S -> A\nA -> ab").unwrap().to_nfa().unwrap();
+        assert!(nfa.simulate("ab"));
+        assert!(!nfa.simulate("a"));
+
+        let cfg = CFG::load_from_str("S -> aSb |").unwrap();
+        assert_eq!(cfg.to_nfa().unwrap_err(), NotRegular);
+    }
+
+    #[test]
+    fn dfa_complement() {
+        let alphabet: BTreeSet<char> = "ab".chars().collect();
+        let nfa = CFG::load_from_str("S -> aS | b").unwrap().to_nfa().unwrap();
+        let dfa = nfa.to_dfa(&alphabet);
+        for input in vec!["b", "ab", "aaab"] {
+            assert!(dfa.accepts(input), "Reject {}", input);
+        }
+        let complement = dfa.complement();
+        for input in vec!["", "a", "ba", "abb", "bb", "aba"] {
+            assert!(complement.accepts(input), "Reject {}", input);
+            assert!(!dfa.accepts(input), "Accept {}", input);
+        }
+        assert!(!complement.accepts("aab"));
+        assert!(!complement.accepts("c"));
+
+        let cfg = complement.to_grammar();
+        assert_eq!(cfg.is_regular(), Some(Linearity::Right));
+        let nfa = cfg.to_nfa().unwrap();
+        for input in vec!["", "a", "ba", "abb", "bb", "aba", "b", "ab", "aaab"] {
+            assert_eq!(nfa.simulate(input), complement.accepts(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn nfa_reverse() {
+        let nfa = CFG::load_from_str("S -> abS | c").unwrap().to_nfa().unwrap();
+        let reversed = nfa.reverse();
+        for input in vec!["c", "cba", "cbaba"] {
+            assert!(reversed.simulate(input), "Reject {}", input);
+        }
+        for input in vec!["", "abc", "cab", "cb"] {
+            assert!(!reversed.simulate(input), "Accept {}", input);
+        }
+    }
+
+    #[test]
+    fn dfa_equivalent() {
+        let alphabet: BTreeSet<char> = "ab".chars().collect();
+        let dfa = |x: &str| CFG::load_from_str(x).unwrap().to_nfa().unwrap().to_dfa(&alphabet);
+        assert!(!dfa("S -> aS | b").equivalent(&dfa("S -> aA | b\nA -> aS")));
+        assert!(dfa("S -> aS | b").equivalent(&dfa("S -> aaS | aA | b\nA -> b")));
+        assert!(dfa("S -> aS | bS |").equivalent(&dfa("S -> A | \nA -> aS | bS")));
+        assert!(!dfa("S -> aS | bS |").equivalent(&dfa("S -> aS | bS | a")));
+    }
+
+    #[test]
+    fn pda_from_grammar() {
+        let pda = CFG::load_from_str("S -> aSb | ab").unwrap().to_pda();
+        for input in vec!["ab", "aabb", "aaaabbbb"] {
+            assert!(pda.accepts(input), "Reject {}", input);
+        }
+        for input in vec!["", "a", "abab", "aab", "abb", "ba"] {
+            assert!(!pda.accepts(input), "Accept {}", input);
+        }
+
+        let test_rules = "
+            S -> (S)S | A
+            A -> B |
+            B -> A
+        ";
+        let pda = CFG::load_from_str(test_rules).unwrap().to_pda();
+        for input in vec!["", "()", "(())()", "((()()))"] {
+            assert!(pda.accepts(input), "Reject {}", input);
+        }
+        for input in vec!["(", "())", ")("] {
+            assert!(!pda.accepts(input), "Accept {}", input);
+        }
+        let pda = pda.with_max_depth(3);
+        assert!(!pda.accepts("(())"));
+
+        let pda = CFG::load_from_str("S -> SS | (S) |").unwrap().to_pda();
+        for input in vec!["", "()", "(()())", "()(())()"] {
+            assert!(pda.accepts(input), "Reject {}", input);
+        }
+        for input in vec!["(()", "())(", "((())", "(()))()"] {
+            assert!(!pda.accepts(input), "Accept {}", input);
+        }
+        let cfg = CFG::load("sample/cfg/brackets.txt").unwrap();
+        let pda = cfg.to_pda();
+        assert!(pda.accepts("(()())()"));
+        assert!(!pda.accepts("(()()))("));
+    }
+}
@@ -1,10 +1,19 @@
+use automata::{Nfa, NotRegular, Pda};
+use cyk::CYKParser;
+use dfa::{State, DFA};
+use ebnf;
+use generator::{Generator, GeneratorError};
 use itertools::join;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use ll1::{Conflict, Ll1Table};
+use parse::{BudgetExceeded, LeftmostSearch, ParseTree};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
 use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Cursor};
 
-#[derive(Debug, Hash, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Ordered by the name and then by the sub-index: 'A' < '<A1>' < '<A2>' < 'B'
+#[derive(Debug, Hash, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Nonterminal {
     pub name: String,
     pub sub_index: u32,
@@ -22,14 +31,15 @@ impl Nonterminal {
         let mut sub_index = 0;
         let mut index = String::new();
         for ch in name.chars().rev() {
-            if !ch.is_numeric() {
+            if !ch.is_digit(10) {
                 break;
             }
             index.insert(0, ch);
         }
         if let Ok(num) = index.parse::<u32>() {
-            let name_chars_count = name.chars().count();
-            name.truncate(name_chars_count - index.len());
+            // the index is made of ASCII digits, so its byte length is the count of chars
+            let name_len = name.len();
+            name.truncate(name_len - index.len());
             sub_index = num;
         }
         Nonterminal::new(name, sub_index)
@@ -37,6 +47,16 @@ impl Nonterminal {
     pub fn inc_sub_index(&self) -> Nonterminal {
         Nonterminal::new(self.name.to_owned(), self.sub_index + 1)
     }
+    /// Returns self or the first sub-indexed variant of it that is not taken yet,
+    /// the returned Nonterminal is marked as taken.
+    pub fn fresh(&self, taken: &mut BTreeSet<Nonterminal>) -> Nonterminal {
+        let mut nonterm = self.clone();
+        while taken.contains(&nonterm) {
+            nonterm = nonterm.inc_sub_index();
+        }
+        taken.insert(nonterm.clone());
+        nonterm
+    }
 }
 impl fmt::Display for Nonterminal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -53,7 +73,8 @@ impl fmt::Display for Nonterminal {
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+/// Ordered by the char
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct Terminal {
     pub symbol: char,
 }
@@ -73,6 +94,32 @@ impl fmt::Display for Terminal {
     }
 }
 
+/// Decides which characters of the rules are terminals,
+/// the names in '<>' are always Nonterminals
+pub trait SymbolClassifier {
+    fn is_terminal(&self, ch: char) -> bool;
+}
+
+/// Uppercase letters of any script are Nonterminals ('S', 'Б'), any other character is
+/// a terminal: lowercase letters, digits, punctuation, the letters of the scripts without
+/// the case (CJK, Arabic, ..) and the characters out of the BMP such as emoji.
+/// A Nonterminal named by an uncased letter is written in '<>' or read by a SymbolClassifier
+pub struct DefaultClassifier;
+
+impl SymbolClassifier for DefaultClassifier {
+    fn is_terminal(&self, ch: char) -> bool {
+        !(ch.is_alphabetic() && ch.is_uppercase())
+    }
+}
+
+impl<F: Fn(char) -> bool> SymbolClassifier for F {
+    fn is_terminal(&self, ch: char) -> bool {
+        self(ch)
+    }
+}
+
+/// All Nonterminals come before all terminals, the BTreeSet-s of the productions
+/// and the fresh names of the transformations depend on this order
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum Symbol {
     N(Nonterminal),
@@ -81,8 +128,11 @@ pub enum Symbol {
 
 impl Symbol {
     pub fn new(c: String) -> Symbol {
+        Symbol::classify(c, &DefaultClassifier)
+    }
+    pub fn classify<C: SymbolClassifier>(c: String, classifier: &C) -> Symbol {
         let chars: Vec<char> = c.chars().collect();
-        if chars.len() != 1 || (chars[0].is_alphabetic() && chars[0].is_uppercase()) {
+        if chars.len() != 1 || !classifier.is_terminal(chars[0]) {
             Symbol::N(Nonterminal::parse(c))
         } else {
             Symbol::T(Terminal::new(chars[0]))
@@ -117,6 +167,30 @@ impl Symbol {
             &Symbol::N(ref x) => Some(&x),
         }
     }
+
+    pub fn as_terminal(&self) -> Option<&Terminal> {
+        match self {
+            &Symbol::T(ref x) => Some(&x),
+            &Symbol::N(_) => None,
+        }
+    }
+
+    pub fn terminal_char(&self) -> Option<char> {
+        self.as_terminal().map(|x| x.symbol)
+    }
+
+    /// Only the Nonterminals named by a single character without a sub-index have it
+    pub fn nonterminal_char(&self) -> Option<char> {
+        let nonterm = match self.as_nonterminal() {
+            Some(x) if x.sub_index == 0 => x,
+            _ => return None,
+        };
+        let mut chars = nonterm.name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Some(ch),
+            _ => None,
+        }
+    }
     pub fn merge(set: &[Symbol]) -> Symbol {
         let name = set.iter().map(|x| format!("{}", x)).collect::<String>();
         Symbol::N(Nonterminal::new(name, 0))
@@ -132,7 +206,49 @@ impl fmt::Display for Symbol {
     }
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+// Symbol is serialized compactly as it is written in the rules: 'a', 'S' or '<S1>'
+impl ::serde::Serialize for Symbol {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> ::serde::Deserialize<'de> for Symbol {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> ::serde::de::Visitor<'de> for Visitor {
+            type Value = Symbol;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("terminal character or Nonterminal name")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Symbol, E>
+            where
+                E: ::serde::de::Error,
+            {
+                if value.is_empty() {
+                    Err(E::custom("empty symbol"))
+                } else {
+                    Ok(Symbol::new(value.to_string()))
+                }
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+/// Ordered by the left side, then by the right side lexicographically
+/// (a prefix comes first) and then by the translation
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct Production {
     pub left: Nonterminal,
     pub right: Vec<Symbol>,
@@ -155,40 +271,303 @@ impl Production {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// An item of the FIRST and FOLLOW sets:
+/// a terminal, the empty string marker or the end of input marker
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum Lookahead {
+    T(Terminal),
+    Epsilon,
+    End,
+}
+
+impl fmt::Display for Lookahead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Lookahead::T(ref t) => write!(f, "{}", t),
+            &Lookahead::Epsilon => write!(f, "ε"),
+            &Lookahead::End => write!(f, "$"),
+        }
+    }
+}
+
+/// The form of the rules of a regular grammar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linearity {
+    /// 'A -> wB' or 'A -> w'
+    Right,
+    /// 'A -> Bw' or 'A -> w'
+    Left,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem of a grammar found by CFG::validate
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: severity,
+            message: message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+/// The summary of a grammar returned by CFG::stats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarStats {
+    pub productions: usize,
+    pub nonterminals: usize,
+    pub terminals: usize,
+    pub max_rhs_len: usize,
+    /// 'A -> ε'
+    pub epsilon_productions: usize,
+    /// 'A -> B'
+    pub unit_productions: usize,
+}
+
+/// The steps of CFG::simplify in the order they are applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimplifyStep {
+    Epsilon,
+    Unit,
+    Useless,
+    Unreachable,
+}
+
+/// The productions a step of CFG::simplify_traced has removed from the grammar
+/// and the ones it has added
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepReport {
+    pub step: SimplifyStep,
+    pub removed: BTreeSet<Production>,
+    pub added: BTreeSet<Production>,
+}
+
+/// An error of loading a grammar, 'line' is 1-based, 0 means the line is unknown
+#[derive(Debug)]
+pub enum CfgError {
+    Io(io::Error),
+    BadRule { line: usize, text: String },
+    TerminalOnLhs { line: usize, text: String },
+    /// 'AB -> c' is not a context-free rule, a long name is written as '<AB>'
+    MultiSymbolLhs {
+        line: usize,
+        text: String,
+        symbols: Vec<Symbol>,
+    },
+    NoRules,
+    NonterminalExists(Nonterminal),
+}
+
+impl CfgError {
+    /// The 1-based line of the rule that failed to parse, if it is known
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            &CfgError::BadRule { line, .. }
+            | &CfgError::TerminalOnLhs { line, .. }
+            | &CfgError::MultiSymbolLhs { line, .. }
+                if line > 0 =>
+            {
+                Some(line)
+            }
+            _ => None,
+        }
+    }
+
+    fn at_line(self, num: usize) -> CfgError {
+        match self {
+            CfgError::BadRule { text, .. } => CfgError::BadRule {
+                line: num,
+                text: text,
+            },
+            CfgError::TerminalOnLhs { text, .. } => CfgError::TerminalOnLhs {
+                line: num,
+                text: text,
+            },
+            CfgError::MultiSymbolLhs { text, symbols, .. } => CfgError::MultiSymbolLhs {
+                line: num,
+                text: text,
+                symbols: symbols,
+            },
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (msg, line, text) = match self {
+            &CfgError::Io(ref e) => return write!(f, "{}", e),
+            &CfgError::NoRules => return write!(f, "Don't see any rule"),
+            &CfgError::NonterminalExists(ref n) => {
+                return write!(f, "Nonterminal {} already exists", n)
+            }
+            &CfgError::BadRule { line, ref text } => ("Bad rule".to_string(), line, text),
+            &CfgError::TerminalOnLhs { line, ref text } => {
+                ("Terminal symbol at LHS".to_string(), line, text)
+            }
+            &CfgError::MultiSymbolLhs {
+                line,
+                ref text,
+                ref symbols,
+            } => {
+                let msg = format!("Several symbols at LHS ({})", join(symbols, ", "));
+                (msg, line, text)
+            }
+        };
+        if line > 0 {
+            write!(f, "{} on line {}: {}", msg, line, text)
+        } else {
+            write!(f, "{}: {}", msg, text)
+        }
+    }
+}
+
+impl Error for CfgError {
+    fn description(&self) -> &str {
+        match self {
+            &CfgError::Io(ref e) => e.description(),
+            &CfgError::BadRule { .. } => "Bad rule",
+            &CfgError::TerminalOnLhs { .. } => "Terminal symbol at LHS",
+            &CfgError::MultiSymbolLhs { .. } => "Several symbols at LHS",
+            &CfgError::NoRules => "Don't see any rule",
+            &CfgError::NonterminalExists(_) => "Nonterminal already exists",
+        }
+    }
+}
+
+impl From<io::Error> for CfgError {
+    fn from(e: io::Error) -> CfgError {
+        CfgError::Io(e)
+    }
+}
+
+/// Grammars are equal if they have the same start symbol and the same set of productions,
+/// it is the structural equality: grammars of the same language may differ in the rules
+/// or just in the names of Nonterminal-s
+#[derive(Debug, Eq, Serialize, Deserialize)]
 pub struct CFG {
     pub start: Nonterminal,
     pub productions: BTreeSet<Production>,
+    // the productions in the order they are read from a file, see CFG::to_source
+    #[serde(skip)]
+    source_order: Vec<Production>,
+}
+
+impl PartialEq for CFG {
+    fn eq(&self, other: &CFG) -> bool {
+        self.start == other.start && self.productions == other.productions
+    }
+}
+/// The line width after which CFG::to_pretty_string moves the alternatives to the next line
+const PRETTY_WIDTH: usize = 80;
+
+/// The terminals that are the metacharacters of the rules (and the comment sign) and
+/// the whitespaces at the ends of the right side are written with a backslash
+fn escape_rhs(right: &[Symbol]) -> String {
+    let last = right.len().saturating_sub(1);
+    let symbols = right.iter().enumerate().map(|(idx, symbol)| match symbol {
+        &Symbol::T(ref t)
+            if "|<>#\\".contains(t.symbol)
+                || (idx == 0 || idx == last) && t.symbol.is_whitespace() =>
+        {
+            format!("\\{}", t.symbol)
+        }
+        _ => symbol.to_string(),
+    });
+    join(symbols, "")
+}
+
+/// Trims the whitespaces around the text, but keeps an escaped trailing whitespace
+fn trim_escaped(text: &str) -> &str {
+    let start = text.find(|x: char| !x.is_whitespace()).unwrap_or(text.len());
+    let mut end = start + text[start..].trim().len();
+    let backslashes = text[start..end].chars().rev().take_while(|&x| x == '\\').count();
+    if backslashes % 2 == 1 {
+        end += text[end..].chars().next().map_or(0, |x| x.len_utf8());
+    }
+    &text[start..end]
+}
+
+/// The text before the comment started by the first '#' not escaped with a backslash
+fn strip_comment(text: &str) -> &str {
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == '#' {
+            return &text[..idx];
+        }
+    }
+    text
+}
+
+/// Splits the text by the separators not escaped with a backslash and trims the parts
+fn split_unescaped<'a>(text: &'a str, sep: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut begin = 0;
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if idx >= begin && text[idx..].starts_with(sep) {
+            parts.push(&text[begin..idx]);
+            begin = idx + sep.len();
+        }
+    }
+    parts.push(&text[begin..]);
+    parts.into_iter().map(trim_escaped).collect()
 }
+
 impl fmt::Display for CFG {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut rules: HashMap<Nonterminal, Vec<String>> = HashMap::new();
-        for rule in self.productions.iter() {
-            let mut chars = match rules.get(&rule.left) {
-                Some(s) => s.clone(),
-                None => Vec::new(),
-            };
-            chars.push(join(&rule.right, ""));
-            rules.insert(rule.left.clone(), chars);
-        }
+        // BTreeMap keeps the rules of the other Nonterminal-s sorted by their names
+        let mut rules: BTreeMap<Nonterminal, Vec<String>> = self
+            .rules_by_lhs()
+            .into_iter()
+            .map(|(left, alternatives)| {
+                let alternatives = alternatives.iter().map(|right| {
+                    if right.is_empty() && f.alternate() {
+                        // '{:#}' prints the empty right side explicitly
+                        "ε".to_string()
+                    } else {
+                        escape_rhs(right)
+                    }
+                });
+                (left, alternatives.collect())
+            }).collect();
         if let Some(mut start) = rules.remove(&self.start) {
             start.sort();
-            if let Err(e) = write!(f, "{} -> {}\n", self.start, join(start, " | ")) {
-                return Err(e);
-            }
-        } else {
-            if rules.is_empty() {
-                eprintln!("Empty rule set: {:?}", self);
-                return write!(f, "{} -> \n", self.start);
-            }
+            write!(f, "{} -> {}\n", self.start, join(start, " | "))?;
+        } else if rules.is_empty() {
+            // the grammar without rules is the start symbol with nothing to derive
+            return write!(f, "{} -> \n", self.start);
         }
-        for rule in self.productions.iter() {
-            if let Some(mut val) = rules.remove(&rule.left) {
-                val.sort();
-                if let Err(e) = write!(f, "{} -> {}\n", rule.left, join(val, " | ")) {
-                    return Err(e);
-                }
-            }
+        for (left, mut val) in rules {
+            val.sort();
+            write!(f, "{} -> {}\n", left, join(val, " | "))?;
         }
         Ok(())
     }
@@ -199,73 +578,164 @@ impl CFG {
         CFG {
             start: start,
             productions: prods,
+            source_order: Vec::new(),
+        }
+    }
+
+    /// The duplicated productions are collected once,
+    /// the order of the productions is kept for CFG::to_source
+    pub fn from_productions<I>(start: Nonterminal, prods: I) -> CFG
+    where
+        I: IntoIterator<Item = Production>,
+    {
+        let mut cfg = CFG::new(start, BTreeSet::new());
+        for rule in prods {
+            if cfg.productions.insert(rule.clone()) {
+                cfg.source_order.push(rule);
+            }
         }
+        cfg
+    }
+
+    /// Returns false if the grammar already has the production
+    pub fn add_production(&mut self, p: Production) -> bool {
+        self.productions.insert(p)
+    }
+
+    /// Returns false if the grammar has no such production,
+    /// the start symbol is kept even if its last rule is removed
+    pub fn remove_production(&mut self, p: &Production) -> bool {
+        self.productions.remove(p)
     }
 
-    pub fn load(input_path: &str) -> io::Result<CFG> {
+    pub fn load(input_path: &str) -> Result<CFG, CfgError> {
         let file = BufReader::new(File::open(input_path)?);
         CFG::load_from_reader(file)
     }
 
-    pub fn load_sdt(input_path: &str) -> io::Result<CFG> {
+    pub fn load_sdt(input_path: &str) -> Result<CFG, CfgError> {
         let file = BufReader::new(File::open(input_path)?);
         CFG::load_sdt_from_reader(file)
     }
 
-    pub fn load_from_reader<R: Sized + BufRead>(r: R) -> io::Result<CFG> {
+    pub fn load_from_reader<R: Sized + BufRead>(r: R) -> Result<CFG, CfgError> {
         CFG::load_cfg_from_reader(r, false)
     }
 
-    pub fn load_sdt_from_reader<R: Sized + BufRead>(r: R) -> io::Result<CFG> {
+    pub fn load_from_str(input: &str) -> Result<CFG, CfgError> {
+        CFG::load_from_reader(Cursor::new(input))
+    }
+
+    pub fn load_sdt_from_reader<R: Sized + BufRead>(r: R) -> Result<CFG, CfgError> {
         CFG::load_cfg_from_reader(r, true)
     }
 
-    pub fn load_cfg_from_reader<R: Sized + BufRead>(r: R, sdt: bool) -> io::Result<CFG> {
+    pub fn load_cfg_from_reader<R: Sized + BufRead>(r: R, sdt: bool) -> Result<CFG, CfgError> {
+        CFG::load_cfg_from_reader_with(r, sdt, &DefaultClassifier)
+    }
+
+    /// The start symbol is the left side of the first rule,
+    /// unless it is named by the '# start: S' directive anywhere in the input
+    pub fn load_cfg_from_reader_with<R: Sized + BufRead, C: SymbolClassifier>(
+        r: R,
+        sdt: bool,
+        classifier: &C,
+    ) -> Result<CFG, CfgError> {
         let mut start: Option<Nonterminal> = None;
+        let mut directive: Option<Nonterminal> = None;
         let mut productions = BTreeSet::new();
-        for line in r.lines() {
+        let mut source_order = Vec::new();
+        for (idx, line) in r.lines().enumerate() {
             let mut text = line?;
-            let rule = text.trim();
-            if rule.is_empty() || rule.starts_with('#') {
+            let rule = trim_escaped(&text);
+            if rule.starts_with('#') {
+                let comment = rule[1..].trim();
+                if comment.starts_with("start:") {
+                    let name = comment["start:".len()..].trim();
+                    match Symbol::classify(name.to_string(), classifier) {
+                        Symbol::N(ref n) if !name.is_empty() => directive = Some(n.clone()),
+                        _ => {
+                            return Err(CfgError::BadRule {
+                                line: idx + 1,
+                                text: rule.to_string(),
+                            })
+                        }
+                    }
+                }
                 continue;
             }
-            let add_productions = CFG::parse_production(&rule, sdt)?;
+            // the rest of the line after '#' is a comment, '\#' is the terminal
+            let rule = trim_escaped(strip_comment(rule));
+            if rule.is_empty() {
+                continue;
+            }
+            let add_productions = CFG::parse_production_with(&rule, sdt, classifier)
+                .map_err(|e| e.at_line(idx + 1))?;
             if productions.is_empty() {
                 // The first valid rule is the start character here
                 start = Some(add_productions[0].left.clone());
             }
-            productions.extend(add_productions.into_iter());
+            for rule in add_productions {
+                if productions.insert(rule.clone()) {
+                    source_order.push(rule);
+                }
+            }
         }
         if let Some(s) = start {
-            Ok(CFG::new(s, productions))
+            let mut cfg = CFG::new(directive.unwrap_or(s), productions);
+            cfg.source_order = source_order;
+            Ok(cfg)
         } else {
-            Err(io::Error::new(io::ErrorKind::Other, "Don't see any rule"))
+            Err(CfgError::NoRules)
         }
     }
 
-    pub fn parse_production(line: &str, sdt: bool) -> io::Result<Vec<Production>> {
+    /// Errors returned from here have no line number, the reader fills it in
+    pub fn parse_production(line: &str, sdt: bool) -> Result<Vec<Production>, CfgError> {
+        CFG::parse_production_with(line, sdt, &DefaultClassifier)
+    }
+
+    pub fn parse_production_with<C: SymbolClassifier>(
+        line: &str,
+        sdt: bool,
+        classifier: &C,
+    ) -> Result<Vec<Production>, CfgError> {
         let mut productions = Vec::new();
-        let rule: Vec<&str> = line.split(" -> ").map(|x| x.trim()).collect();
+        let bad_rule = || CfgError::BadRule {
+            line: 0,
+            text: line.to_string(),
+        };
+        let rule = split_unescaped(line, " -> ");
         if rule.len() != 2 {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Bad rule: {}", line),
-            ));
+            return Err(bad_rule());
         }
 
         if rule[0].chars().count() == 0 {
-            return Err(io::Error::new(io::ErrorKind::Other, "Missing left Symbol"));
+            return Err(bad_rule());
         }
-        let left = Symbol::new(rule[0].to_string());
+        let symbols = CFG::parse_rhs_with(rule[0], classifier).map_err(|_| bad_rule())?;
+        if symbols.len() > 1 {
+            return Err(CfgError::MultiSymbolLhs {
+                line: 0,
+                text: line.to_string(),
+                symbols: symbols,
+            });
+        }
+        let left = Symbol::classify(rule[0].to_string(), classifier);
         if left.is_terminal() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Terminal symbol at LHS: {}", line),
-            ));
+            return Err(CfgError::TerminalOnLhs {
+                line: 0,
+                text: line.to_string(),
+            });
         }
         let left = left.as_nonterminal().unwrap();
-        for rhs in rule[1].split('|').map(|x| x.trim()) {
-            let symbols = CFG::parse_rhs(rhs)?;
+        for rhs in split_unescaped(rule[1], "|") {
+            // a standalone 'ε' is an explicit form of the empty right side
+            let symbols = if rhs == "ε" {
+                Vec::new()
+            } else {
+                CFG::parse_rhs_with(rhs, classifier).map_err(|_| bad_rule())?
+            };
             let mut prod = Production::new(left.clone(), symbols);
             productions.push(prod);
         }
@@ -273,10 +743,32 @@ impl CFG {
     }
 
     pub fn parse_rhs(rhs: &str) -> io::Result<Vec<Symbol>> {
+        CFG::parse_rhs_with(rhs, &DefaultClassifier)
+    }
+
+    pub fn parse_rhs_with<C: SymbolClassifier>(
+        rhs: &str,
+        classifier: &C,
+    ) -> io::Result<Vec<Symbol>> {
         let mut name = String::new();
         let mut symbols = Vec::new();
         let mut read_long_name = false;
+        let mut escaped = false;
         for ch in rhs.chars() {
+            if escaped {
+                // a character after the backslash is always a terminal
+                escaped = false;
+                if read_long_name {
+                    name.push(ch);
+                } else {
+                    symbols.push(Symbol::T(Terminal::new(ch)));
+                }
+                continue;
+            }
+            if ch == '\\' {
+                escaped = true;
+                continue;
+            }
             if ch == '>' {
                 if !read_long_name {
                     return Err(io::Error::new(
@@ -297,7 +789,7 @@ impl CFG {
             }
             name.push(ch);
             if !read_long_name {
-                symbols.push(Symbol::new(name.clone()));
+                symbols.push(Symbol::classify(name.clone(), classifier));
                 name.truncate(0);
             }
         }
@@ -307,11 +799,18 @@ impl CFG {
                 format!("Unterminated Nonterminal symbol name, expect '>'"),
             ));
         }
+        if escaped {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Nothing to escape at the end of the rule"),
+            ));
+        }
         Ok(symbols)
     }
 
-    pub fn get_terminals(&self) -> HashSet<Terminal> {
-        let mut term = HashSet::new();
+    /// The terminals used at the right sides of the rules
+    pub fn terminals(&self) -> BTreeSet<Terminal> {
+        let mut term = BTreeSet::new();
         for rule in &self.productions {
             term.extend(
                 rule.right
@@ -321,13 +820,14 @@ impl CFG {
                     .map(|x| match x {
                         Symbol::T(n) => n,
                         _ => unreachable!(),
-                    }).collect::<HashSet<Terminal>>(),
+                    }).collect::<BTreeSet<Terminal>>(),
             );
         }
         term
     }
 
-    pub fn get_variables(&self) -> BTreeSet<Nonterminal> {
+    /// The Nonterminals at the left and the right sides of the rules
+    pub fn nonterminals(&self) -> BTreeSet<Nonterminal> {
         let mut vars = BTreeSet::new();
         for rule in &self.productions {
             vars.extend(
@@ -345,28 +845,262 @@ impl CFG {
         vars
     }
 
-    pub fn get_nullable(&self) -> HashSet<Nonterminal> {
-        let mut nullable: HashSet<Nonterminal> = HashSet::new();
+    /// Nonterminals used at the right side of some rule but never defined by a rule
+    pub fn undefined_nonterminals(&self) -> HashSet<Nonterminal> {
+        let defined: HashSet<&Nonterminal> = self.productions.iter().map(|x| &x.left).collect();
+        self.productions
+            .iter()
+            .flat_map(|x| x.right.iter().filter_map(|s| s.as_nonterminal()))
+            .filter(|x| !defined.contains(x))
+            .cloned()
+            .collect()
+    }
+
+    pub fn stats(&self) -> GrammarStats {
+        let rules = &self.productions;
+        GrammarStats {
+            productions: rules.len(),
+            nonterminals: self.nonterminals().len(),
+            terminals: self.terminals().len(),
+            max_rhs_len: rules.iter().map(|x| x.right.len()).max().unwrap_or(0),
+            epsilon_productions: rules.iter().filter(|x| x.right.is_empty()).count(),
+            unit_productions: rules
+                .iter()
+                .filter(|x| x.right.len() == 1 && x.right[0].is_nonterminal())
+                .count(),
+        }
+    }
+
+    /// Collects the problems of the grammar: undefined start symbol and Nonterminal-s,
+    /// unreachable and non-generating Nonterminal-s.
+    /// An undefined single letter is likely meant as a terminal of the alphabet,
+    /// its message suggests a custom SymbolClassifier
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if !self.productions.iter().any(|x| x.left == self.start) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                format!("start symbol {} has no rules", self.start),
+            ));
+        }
+        let undefined = self.undefined_nonterminals();
+        let reachable = self.reachable_symbols();
+        let generating = self.generating_symbols();
+        for nonterm in self.nonterminals() {
+            if undefined.contains(&nonterm) {
+                let rule = self
+                    .productions
+                    .iter()
+                    .find(|x| x.right.iter().any(|s| s.is_eq_nonterm(&nonterm)))
+                    .unwrap();
+                let mut message = format!(
+                    "undefined Nonterminal {} in the rule '{} -> {}'",
+                    nonterm,
+                    rule.left,
+                    join(&rule.right, "")
+                );
+                if nonterm.sub_index == 0 && nonterm.name.chars().count() == 1 {
+                    message.push_str(", use a SymbolClassifier to read it as a terminal");
+                }
+                diagnostics.push(Diagnostic::new(Severity::Error, message));
+                continue;
+            }
+            if !reachable.contains(&nonterm) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    format!("unreachable Nonterminal {}", nonterm),
+                ));
+            }
+            if !generating.contains(&nonterm) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    format!("Nonterminal {} does not derive any terminal string", nonterm),
+                ));
+            }
+        }
+        diagnostics
+    }
+
+    /// Returns the Linearity if all rules are right-linear or all rules are left-linear,
+    /// a grammar without Nonterminal-s at the right sides is treated as right-linear
+    pub fn is_regular(&self) -> Option<Linearity> {
+        let linear = |pos: fn(&Production) -> Option<&Symbol>| {
+            self.productions.iter().all(|rule| {
+                match rule.right.iter().filter(|x| x.is_nonterminal()).count() {
+                    0 => true,
+                    1 => pos(rule).map_or(false, |x| x.is_nonterminal()),
+                    _ => false,
+                }
+            })
+        };
+        if linear(|rule| rule.right.last()) {
+            Some(Linearity::Right)
+        } else if linear(|rule| rule.right.first()) {
+            Some(Linearity::Left)
+        } else {
+            None
+        }
+    }
+
+    /// NFA accepting the language of a right-linear grammar
+    pub fn to_nfa(&self) -> Result<Nfa, NotRegular> {
+        Nfa::from_grammar(self)
+    }
+
+    /// Right-linear grammar of the strings over the alphabet that are not in L(self),
+    /// built by complementing the DFA of the grammar
+    pub fn complement(&self, alphabet: &HashSet<char>) -> Result<CFG, NotRegular> {
+        let alphabet = alphabet.iter().cloned().collect();
+        Ok(self.to_nfa()?.to_dfa(&alphabet).complement().to_grammar())
+    }
+
+    /// NFA accepting the language of a right-linear or a left-linear grammar,
+    /// the left-linear one is read as the right-linear grammar of the reversed strings
+    fn regular_nfa(&self) -> Result<Nfa, NotRegular> {
+        match self.is_regular() {
+            Some(Linearity::Right) => self.to_nfa(),
+            Some(Linearity::Left) => {
+                let productions = self.productions.iter().map(|rule| {
+                    Production::new(rule.left.clone(), rule.right.iter().rev().cloned().collect())
+                });
+                Ok(CFG::from_productions(self.start.clone(), productions).to_nfa()?.reverse())
+            }
+            None => Err(NotRegular),
+        }
+    }
+
+    /// The equality of the languages of two regular grammars (right-linear or left-linear),
+    /// their DFAs over the terminals of both grammars are checked for the equivalence
+    pub fn language_equals_regular(&self, other: &CFG) -> Result<bool, NotRegular> {
+        let alphabet: BTreeSet<char> = self
+            .terminals()
+            .into_iter()
+            .chain(other.terminals())
+            .map(|x| x.symbol)
+            .collect();
+        let dfa = self.regular_nfa()?.to_dfa(&alphabet);
+        Ok(dfa.equivalent(&other.regular_nfa()?.to_dfa(&alphabet)))
+    }
+
+    /// Single state PDA simulating the leftmost derivations of the grammar
+    pub fn to_pda(&self) -> Pda {
+        Pda::from_grammar(self)
+    }
+
+    /// Nonterminals deriving the empty string
+    pub fn nullable_symbols(&self) -> HashSet<Nonterminal> {
+        // rule N -> epsilon or
+        // if the rule contains only Nonterminal-s and they all lead to epsilon
+        InternedRules::new(self).fixpoint(|rule| !rule.has_terminal)
+    }
+
+    /// The empty string is in the language iff the start symbol is nullable
+    pub fn language_contains_epsilon(&self) -> bool {
+        self.nullable_symbols().contains(&self.start)
+    }
+
+    pub fn first_sets(&self) -> HashMap<Nonterminal, HashSet<Lookahead>> {
+        let nullable = self.nullable_symbols();
+        let mut first: HashMap<Nonterminal, HashSet<Lookahead>> = self
+            .nonterminals()
+            .into_iter()
+            .map(|x| {
+                let mut set = HashSet::new();
+                if nullable.contains(&x) {
+                    set.insert(Lookahead::Epsilon);
+                }
+                (x, set)
+            }).collect();
         let mut changed = true;
         while changed {
             changed = false;
             for rule in &self.productions {
-                // rule N -> epsilon or
-                // if the rule contains only Nonterminal-s and they all lead to epsilon
-                if rule.right.is_empty() || rule.right.iter().fold(true, |acc, x| {
-                    if !acc {
-                        acc
-                    } else {
-                        x.is_nonterminal() && nullable.contains(x.as_nonterminal().unwrap())
-                    }
-                }) {
-                    if nullable.insert(rule.left.clone()) {
+                let rule_first = CFG::first_of(&first, &rule.right);
+                let set = first.get_mut(&rule.left).unwrap();
+                for item in rule_first {
+                    if set.insert(item) {
                         changed = true;
                     }
                 }
             }
         }
-        return nullable;
+        first
+    }
+
+    pub fn follow_sets(&self) -> HashMap<Nonterminal, HashSet<Lookahead>> {
+        let first = self.first_sets();
+        let mut follow: HashMap<Nonterminal, HashSet<Lookahead>> = self
+            .nonterminals()
+            .into_iter()
+            .map(|x| (x, HashSet::new()))
+            .collect();
+        follow
+            .entry(self.start.clone())
+            .or_insert_with(HashSet::new)
+            .insert(Lookahead::End);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &self.productions {
+                for (idx, sym) in rule.right.iter().enumerate() {
+                    let nonterm = match sym.as_nonterminal() {
+                        Some(n) => n,
+                        None => continue,
+                    };
+                    let mut items = CFG::first_of(&first, &rule.right[idx + 1..]);
+                    if items.remove(&Lookahead::Epsilon) {
+                        // the rest of the rule is nullable
+                        items.extend(follow[&rule.left].iter().cloned());
+                    }
+                    let set = follow.get_mut(nonterm).unwrap();
+                    for item in items {
+                        if set.insert(item) {
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        follow
+    }
+
+    /// LL(1) parse table or all of the FIRST/FIRST and FIRST/FOLLOW conflicts
+    pub fn ll1_table(&self) -> Result<Ll1Table, Vec<Conflict>> {
+        Ll1Table::new(self)
+    }
+
+    /// FIRST of the sequence of symbols according to the FIRST sets of the Nonterminals
+    pub fn first_of(
+        first: &HashMap<Nonterminal, HashSet<Lookahead>>,
+        seq: &[Symbol],
+    ) -> HashSet<Lookahead> {
+        let mut result = HashSet::new();
+        for sym in seq {
+            match sym {
+                &Symbol::T(ref t) => {
+                    result.insert(Lookahead::T(t.clone()));
+                    return result;
+                }
+                &Symbol::N(ref n) => {
+                    let nonterm_first = match first.get(n) {
+                        Some(set) => set,
+                        None => return result,
+                    };
+                    result.extend(
+                        nonterm_first
+                            .iter()
+                            .filter(|&x| x != &Lookahead::Epsilon)
+                            .cloned(),
+                    );
+                    if !nonterm_first.contains(&Lookahead::Epsilon) {
+                        return result;
+                    }
+                }
+            }
+        }
+        // every symbol of the sequence is nullable
+        result.insert(Lookahead::Epsilon);
+        result
     }
 
     pub fn simplify(&self) -> CFG {
@@ -376,8 +1110,58 @@ impl CFG {
             .remove_unreachable_rules()
     }
 
+    /// CFG::simplify followed by merging the Nonterminals with the same alternatives
+    /// into one of them (the start symbol or the least name), until there are no such ones.
+    /// The alternatives that differ only by a nullable Nonterminal are made equal by simplify
+    pub fn normalize(&self) -> CFG {
+        let mut cfg = self.simplify();
+        loop {
+            let mut same: BTreeMap<BTreeSet<Vec<Symbol>>, Vec<Nonterminal>> = BTreeMap::new();
+            for (left, alternatives) in cfg.alternatives_map() {
+                same.entry(alternatives).or_insert_with(Vec::new).push(left);
+            }
+            let mut renames: HashMap<Nonterminal, Nonterminal> = HashMap::new();
+            for names in same.into_iter().map(|x| x.1).filter(|x| x.len() > 1) {
+                let keep = if names.contains(&cfg.start) {
+                    cfg.start.clone()
+                } else {
+                    names[0].clone()
+                };
+                for name in names.into_iter().filter(|x| x != &keep) {
+                    renames.insert(name, keep.clone());
+                }
+            }
+            if renames.is_empty() {
+                return cfg;
+            }
+            cfg = cfg.rename_with(|x| renames.get(x).cloned().unwrap_or_else(|| x.clone()));
+        }
+    }
+
+    /// CFG::simplify with the changes of the productions made by every step
+    pub fn simplify_traced(&self) -> (CFG, Vec<StepReport>) {
+        let steps: Vec<(SimplifyStep, fn(&CFG) -> CFG)> = vec![
+            (SimplifyStep::Epsilon, CFG::remove_epsilon_rules),
+            (SimplifyStep::Unit, CFG::remove_unit_rules),
+            (SimplifyStep::Useless, CFG::remove_useless_rules),
+            (SimplifyStep::Unreachable, CFG::remove_unreachable_rules),
+        ];
+        let mut cfg = CFG::new(self.start.clone(), self.productions.clone());
+        let mut reports = Vec::new();
+        for (step, apply) in steps {
+            let next = apply(&cfg);
+            reports.push(StepReport {
+                step: step,
+                removed: cfg.productions.difference(&next.productions).cloned().collect(),
+                added: next.productions.difference(&cfg.productions).cloned().collect(),
+            });
+            cfg = next;
+        }
+        (cfg, reports)
+    }
+
     pub fn remove_epsilon_rules(&self) -> CFG {
-        let nullable = self.get_nullable();
+        let nullable = self.nullable_symbols();
 
         let mut new_rules = BTreeSet::new();
         self.productions.iter().for_each(|rule| {
@@ -438,15 +1222,29 @@ impl CFG {
         CFG::new(start, new_rules)
     }
 
+    /// Drops the rules 'A -> A' that derive nothing new, the other unit rules are kept
+    pub fn remove_self_loops(&self) -> CFG {
+        let productions = self
+            .productions
+            .iter()
+            .filter(|x| !(x.right.len() == 1 && x.right[0].is_eq_nonterm(&x.left)))
+            .cloned()
+            .collect();
+        CFG::new(self.start.clone(), productions)
+    }
+
+    /// Every Nonterminal gets the non-unit rules of the Nonterminals reachable from it
+    /// by the unit rules, the closure collapses the unit cycles like 'A -> B', 'B -> A'
+    /// and all the unit rules including 'A -> A' are dropped
     pub fn remove_unit_rules(&self) -> CFG {
-        let mut unit_sets = self
-            .get_variables()
+        let variables = self.nonterminals();
+        let mut unit_sets = variables
             .iter()
             .cloned()
             .map(|x| (x.clone(), vec![x].into_iter().collect()))
             .collect::<HashMap<Nonterminal, HashSet<Nonterminal>>>();
 
-        for nonterm in &self.get_variables() {
+        for nonterm in &variables {
             let mut set = unit_sets.get_mut(nonterm).unwrap();
             let mut changed = true;
             while changed {
@@ -479,41 +1277,139 @@ impl CFG {
                 }
             }
         }
-        let mut changed = true;
-        while changed {
-            changed = false;
-        }
         CFG::new(self.start.clone(), new_rules)
     }
 
-    pub fn remove_useless_rules(&self) -> CFG {
-        let mut usefull_nonterminals = BTreeSet::new();
-        let mut changed = true;
-        while changed {
-            changed = false;
-            for rule in &self.productions {
-                let right_nonterm_set: BTreeSet<Nonterminal> = rule
-                    .right
-                    .iter()
-                    .cloned()
-                    .filter(|x| x.is_nonterminal())
-                    .map(|x| match x {
-                        Symbol::N(n) => n,
-                        _ => unreachable!(),
-                    }).collect();
-                if right_nonterm_set.is_empty()
-                    || right_nonterm_set.is_subset(&usefull_nonterminals)
-                {
-                    // if rule contains only terminals or all Nonterminals can be generated
-                    if usefull_nonterminals.insert(rule.left.clone()) {
-                        changed = true;
+    /// A grammar without terminal strings has the non-generating start symbol
+    pub fn is_empty_language(&self) -> bool {
+        !self.generating_symbols().contains(&self.start)
+    }
+
+    /// Without epsilon, unit and useless rules the language is infinite
+    /// iff there is a cycle in the dependency graph of the Nonterminals
+    pub fn is_finite_language(&self) -> bool {
+        self.simplify().dependency_cycles().is_empty()
+    }
+
+    /// The Nonterminals used at the right sides of the rules of every Nonterminal,
+    /// the ones without rules are the nodes without edges
+    pub fn dependency_graph(&self) -> HashMap<Nonterminal, HashSet<Nonterminal>> {
+        let mut graph: HashMap<Nonterminal, HashSet<Nonterminal>> = HashMap::new();
+        for rule in &self.productions {
+            let targets: Vec<&Nonterminal> =
+                rule.right.iter().filter_map(|x| x.as_nonterminal()).collect();
+            for &target in &targets {
+                graph.entry(target.clone()).or_insert_with(HashSet::new);
+            }
+            graph
+                .entry(rule.left.clone())
+                .or_insert_with(HashSet::new)
+                .extend(targets.into_iter().cloned());
+        }
+        graph
+    }
+
+    /// The strongly connected components of the dependency graph
+    /// that are cycles: of several Nonterminals or a self-loop.
+    /// The Nonterminals of a component and the components are sorted by name
+    pub fn dependency_cycles(&self) -> Vec<Vec<Nonterminal>> {
+        let graph = self.dependency_graph();
+
+        // Tarjan's algorithm with an explicit stack of the nodes and their successors left,
+        // the recursion overflows the call stack on the long dependency chains
+        struct Search<'a> {
+            graph: &'a HashMap<Nonterminal, HashSet<Nonterminal>>,
+            index: HashMap<&'a Nonterminal, usize>,
+            low: HashMap<&'a Nonterminal, usize>,
+            stack: Vec<&'a Nonterminal>,
+            on_stack: HashSet<&'a Nonterminal>,
+            cycles: Vec<Vec<Nonterminal>>,
+        }
+        fn enter<'a>(search: &mut Search<'a>, node: &'a Nonterminal) {
+            let idx = search.index.len();
+            search.index.insert(node, idx);
+            search.low.insert(node, idx);
+            search.stack.push(node);
+            search.on_stack.insert(node);
+        }
+        fn leave<'a>(search: &mut Search<'a>, node: &'a Nonterminal) {
+            if search.low[node] != search.index[node] {
+                return;
+            }
+            let mut component = Vec::new();
+            while let Some(top) = search.stack.pop() {
+                search.on_stack.remove(top);
+                component.push(top.clone());
+                if top == node {
+                    break;
+                }
+            }
+            if component.len() > 1 || search.graph[node].contains(node) {
+                component.sort();
+                search.cycles.push(component);
+            }
+        }
+        fn connect<'a>(search: &mut Search<'a>, root: &'a Nonterminal) {
+            let graph = search.graph;
+            enter(search, root);
+            let mut frames = vec![(root, graph[root].iter())];
+            loop {
+                let (node, next) = match frames.last_mut() {
+                    Some(&mut (node, ref mut successors)) => (node, successors.next()),
+                    None => return,
+                };
+                match next {
+                    Some(next) if !search.index.contains_key(next) => {
+                        enter(search, next);
+                        frames.push((next, graph[next].iter()));
+                    }
+                    Some(next) => {
+                        if search.on_stack.contains(next) {
+                            let low = search.low[node].min(search.index[next]);
+                            search.low.insert(node, low);
+                        }
+                    }
+                    None => {
+                        frames.pop();
+                        if let Some(&(parent, _)) = frames.last() {
+                            let low = search.low[parent].min(search.low[node]);
+                            search.low.insert(parent, low);
+                        }
+                        leave(search, node);
                     }
                 }
             }
         }
+
+        let mut search = Search {
+            graph: &graph,
+            index: HashMap::new(),
+            low: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            cycles: Vec::new(),
+        };
+        for node in graph.keys() {
+            if !search.index.contains_key(node) {
+                connect(&mut search, node);
+            }
+        }
+        let mut cycles = search.cycles;
+        cycles.sort();
+        cycles
+    }
+
+    /// Nonterminals deriving at least one terminal string (including the empty one)
+    pub fn generating_symbols(&self) -> HashSet<Nonterminal> {
+        // if rule contains only terminals or all Nonterminals can be generated
+        InternedRules::new(self).fixpoint(|_| true)
+    }
+
+    pub fn remove_useless_rules(&self) -> CFG {
+        let usefull_nonterminals = self.generating_symbols();
         let mut productions = BTreeSet::new();
         for rule in &self.productions {
-            let right_nonterm_set: BTreeSet<Nonterminal> = rule
+            let right_nonterm_set: HashSet<Nonterminal> = rule
                 .right
                 .iter()
                 .cloned()
@@ -530,15 +1426,16 @@ impl CFG {
         CFG::new(self.start.clone(), productions)
     }
 
-    pub fn remove_unreachable_rules(&self) -> CFG {
-        let mut reachable_symbols: HashSet<Symbol> = HashSet::new();
-        reachable_symbols.insert(Symbol::N(self.start.clone()));
+    /// Nonterminals that occur in some sentential form derived from the start symbol
+    pub fn reachable_symbols(&self) -> HashSet<Nonterminal> {
+        let mut reachable_symbols: HashSet<Nonterminal> = HashSet::new();
+        reachable_symbols.insert(self.start.clone());
         let mut changed = true;
         while changed {
             changed = false;
             for rule in &self.productions {
-                if reachable_symbols.contains(&Symbol::N(rule.left.clone())) {
-                    for s in &rule.right {
+                if reachable_symbols.contains(&rule.left) {
+                    for s in rule.right.iter().filter_map(|x| x.as_nonterminal()) {
                         if reachable_symbols.insert(s.clone()) {
                             changed = true;
                         }
@@ -546,14 +1443,18 @@ impl CFG {
                 }
             }
         }
-        let mut productions = BTreeSet::new();
-        for rule in &self.productions {
-            let mut right_set: HashSet<Symbol> = rule.right.iter().cloned().collect();
-            right_set.insert(Symbol::N(rule.left.clone()));
-            if right_set.is_subset(&reachable_symbols) {
-                productions.insert(rule.clone());
-            }
-        }
+        reachable_symbols
+    }
+
+    pub fn remove_unreachable_rules(&self) -> CFG {
+        let reachable_symbols = self.reachable_symbols();
+        // all symbols at the right side of a rule with reachable left side are reachable too
+        let productions = self
+            .productions
+            .iter()
+            .filter(|rule| reachable_symbols.contains(&rule.left))
+            .cloned()
+            .collect();
         CFG::new(self.start.clone(), productions)
     }
 
@@ -567,7 +1468,7 @@ impl CFG {
                 .any(|x| x.as_nonterminal() == Some(&self.start))
         });
         if start_in_rhs {
-            let vars = self.get_variables();
+            let vars = self.nonterminals();
             start = start.inc_sub_index();
             while vars.contains(&start) {
                 start = start.inc_sub_index();
@@ -690,242 +1591,2793 @@ impl CFG {
         CFG::new(cfg.start, productions)
     }
 
-    pub fn greibach(&self) -> CFG {
-        let cfg = self.chomsky();
-        let cfg = cfg.eliminate_left_recursion();
-        CFG::new(self.start.clone(), self.productions.clone())
+    /// The empty rule is allowed only for the start symbol that is not used on the right side
+    fn has_only_start_epsilon(&self) -> bool {
+        let start_epsilon = self
+            .productions
+            .iter()
+            .any(|x| x.right.is_empty() && x.left == self.start);
+        self.productions
+            .iter()
+            .all(|x| !x.right.is_empty() || x.left == self.start)
+            && !(start_epsilon && self
+                .productions
+                .iter()
+                .any(|x| x.right.iter().any(|s| s.is_eq_nonterm(&self.start))))
     }
 
-    pub fn eliminate_left_recursion(&self) -> CFG {
-        CFG::new(self.start.clone(), self.productions.clone())
+    /// Every production is 'A -> BC', 'A -> a' or 'S -> ε'
+    pub fn is_in_cnf(&self) -> bool {
+        self.has_only_start_epsilon() && self.productions.iter().all(|x| match x.right.len() {
+            0 => true,
+            1 => x.right[0].is_terminal(),
+            2 => x.right.iter().all(|s| s.is_nonterminal()),
+            _ => false,
+        })
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use self::super::*;
-    use std::io::Cursor;
 
-    #[test]
-    fn load_cfg() {
-        let productions = vec![
-            Production::new(
-                Nonterminal::new("S".to_string(), 2),
-                vec![
-                    Symbol::N(Nonterminal::new("S".to_string(), 1)),
-                    Symbol::N(Nonterminal::new("Some".to_string(), 0)),
-                    Symbol::T(Terminal { symbol: 'a' }),
-                ],
-            ),
-            Production::new(
-                Nonterminal::new("S".to_string(), 2),
-                vec![
-                    Symbol::N(Nonterminal::new("s".to_string(), 0)),
-                    Symbol::N(Nonterminal::new("S".to_string(), 0)),
-                    Symbol::T(Terminal { symbol: 'a' }),
-                ],
-            ),
-        ];
-        let expected = CFG {
-            start: productions[0].left.clone(),
-            productions: productions.into_iter().collect(),
+    /// Every production is 'A -> aB1..Bn' or 'S -> ε'
+    pub fn is_in_gnf(&self) -> bool {
+        let is_gnf_rule = |x: &Production| match x.right.split_first() {
+            None => true,
+            Some((first, rest)) => first.is_terminal() && rest.iter().all(|s| s.is_nonterminal()),
         };
-        let test_definition = "<S2> -> <S1><Some>a | <s>Sa\n";
-        let cfg = CFG::load_from_reader(Cursor::new(test_definition)).unwrap();
-        assert_eq!(cfg.start, expected.start);
-        assert_eq!(cfg.productions, expected.productions);
-        assert_eq!(format!("{}", cfg), test_definition);
-        let text = Cursor::new("<a> -> ||||");
-        assert!(CFG::load_from_reader(text).is_ok());
+        self.has_only_start_epsilon() && self.productions.iter().all(is_gnf_rule)
     }
 
-    #[test]
-    fn load_mailformed_cfg() {
-        let text = Cursor::new("S -> <");
-        assert!(CFG::load_from_reader(text).is_err(), "Eat unexpected '<'");
-        let text = Cursor::new("S -> <<a>");
-        assert!(CFG::load_from_reader(text).is_err(), "Eat unexpected '<'");
-        let text = Cursor::new("S -> >");
-        assert!(CFG::load_from_reader(text).is_err(), "Eat unexpected '>'");
-        let text = Cursor::new("S -> <a>>");
-        assert!(CFG::load_from_reader(text).is_err(), "Eat unexpected '>'");
-        let text = Cursor::new(" -> <a>");
-        assert!(CFG::load_from_reader(text).is_err(), "Missing left Symbol");
-        let text = Cursor::new("a -> ");
-        assert!(CFG::load_from_reader(text).is_err(), "Terminal at LHS");
+    /// Chomsky Normal Form where every new Nonterminal is guaranteed to be fresh:
+    /// every production is 'A -> BC', 'A -> a' or 'S -> ε'
+    pub fn to_cnf(&self) -> CFG {
+        // START, DEL, UNIT and cleanup of the useless symbols
+        let cfg = self
+            .remove_start_from_rhs()
+            .remove_epsilon_rules()
+            .remove_unit_rules()
+            .remove_useless_rules()
+            .remove_unreachable_rules();
+        let mut taken = cfg.nonterminals();
+
+        // TERM: replace terminals in long rules with 'Na -> a' Nonterminals
+        let mut term_rules: HashMap<Symbol, Nonterminal> = HashMap::new();
+        let mut new_productions = BTreeSet::new();
+        for rule in &cfg.productions {
+            if rule.right.len() < 2 {
+                new_productions.insert(rule.clone());
+                continue;
+            }
+            let mut new_rule = rule.clone();
+            for sym in new_rule.right.iter_mut() {
+                if sym.is_terminal() {
+                    let left = term_rules
+                        .entry(sym.clone())
+                        .or_insert_with(|| {
+                            Nonterminal::new(format!("{}", sym), 0).fresh(&mut taken)
+                        }).clone();
+                    *sym = Symbol::N(left);
+                }
+            }
+            new_productions.insert(new_rule);
+        }
+        for (sym, left) in term_rules {
+            new_productions.insert(Production::new(left, vec![sym]));
+        }
+
+        // BIN: split the long rules into the chains of the binary ones
+        CFG::new(cfg.start, new_productions).binarize()
     }
 
-    #[test]
-    fn remove_epsilon() {
-        let test_rules = r#"
-            S -> AaB | aB | cC
-            A -> AB | a | b | B
-            B -> Ba |
-            C -> AB | c
-        "#;
-        let expected = format!(
-            "{}\n",
-            join(
-                vec![
-                    "S -> Aa | AaB | a | aB | c | cC",
-                    "A -> AB | B | a | b",
-                    "B -> Ba | a",
-                    "C -> A | AB | B | c",
-                ],
-                "\n"
-            )
-        );
-        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
-        assert_eq!(format!("{}", cfg.remove_epsilon_rules()), expected);
+    /// The BIN step of CNF: 'A -> X1X2..Xn' with n > 2 is split into the chain
+    /// 'A -> X1<A1>', '<A1> -> X2<A2>', .., the chains of the equal tails are shared.
+    /// The terminals and the empty rules are kept as is
+    pub fn binarize(&self) -> CFG {
+        let mut taken = self.nonterminals();
+        taken.insert(self.start.clone());
+        let mut chains: HashMap<Vec<Symbol>, Nonterminal> = HashMap::new();
+        let mut productions = BTreeSet::new();
+        for rule in &self.productions {
+            if rule.right.len() <= 2 {
+                productions.insert(rule.clone());
+                continue;
+            }
+            let mut left = rule.left.clone();
+            let mut right = rule.right.clone();
+            loop {
+                if right.len() <= 2 {
+                    productions.insert(Production::new(left, right));
+                    break;
+                }
+                let tail = right.split_off(1);
+                // the same tail is derived by the same chain Nonterminal
+                let (tail_left, known) = match chains.get(&tail) {
+                    Some(nonterm) => (nonterm.clone(), true),
+                    None => (rule.left.fresh(&mut taken), false),
+                };
+                right.push(Symbol::N(tail_left.clone()));
+                productions.insert(Production::new(left, right));
+                if known {
+                    break;
+                }
+                chains.insert(tail.clone(), tail_left.clone());
+                left = tail_left;
+                right = tail;
+            }
+        }
+        CFG::new(self.start.clone(), productions)
     }
 
-    #[test]
-    fn remove_units() {
-        let test_rules = "
-            Я -> AaB | aB | cC
-            A -> AB | a | b | B
-            B -> Ba |
-            C -> AB | c
-        ";
-        let expected = format!(
-            "{}\n",
-            join(
-                vec![
-                    "Я -> Aa | AaB | a | aB | c | cC",
-                    "A -> AB | Ba | a | b",
-                    "B -> Ba | a",
-                    "C -> AB | Ba | a | b | c",
-                ],
-                "\n"
-            )
-        );
+    /// Bounded search for a string with two distinct leftmost derivations.
+    /// The ambiguity is undecidable in general, so `None` only means that
+    /// there is no such string not longer than `max_len`.
+    ///
+    /// Unlike `Generator::canonical`, which keeps a copy of a form per derivation
+    /// and bounds the form length only, the equal forms are merged here with
+    /// their derivation counts and a form is dropped when the shortest string
+    /// it derives is too long. So every kept form is a part of a derivation of
+    /// a short string, and the unit and ε cycles end either in a found string
+    /// or in the dropped forms, the search always stops
+    pub fn is_ambiguous(&self, max_len: usize) -> Option<Vec<Symbol>> {
+        let cfg = self.remove_useless_rules().remove_unreachable_rules();
+        let shortest: HashMap<Nonterminal, usize> = cfg
+            .shortest_strings()
+            .into_iter()
+            .map(|(n, s)| (n, s.len()))
+            .collect();
+        let rules = cfg.alternatives_map();
 
-        let cfg = CFG::load_from_reader(Cursor::new(test_rules))
-            .unwrap()
-            .remove_epsilon_rules();
-        assert_eq!(format!("{}", cfg.remove_unit_rules()), expected);
+        // sentential forms of the same derivation length with their derivations count
+        let mut forms: BTreeMap<Vec<Symbol>, u64> = BTreeMap::new();
+        forms.insert(vec![Symbol::N(cfg.start.clone())], 1);
+        let mut derivations: HashMap<Vec<Symbol>, u64> = HashMap::new();
+        while !forms.is_empty() {
+            let mut next_forms = BTreeMap::new();
+            for (form, count) in forms {
+                let idx = match form.iter().position(|x| x.is_nonterminal()) {
+                    Some(idx) => idx,
+                    None => {
+                        let total = derivations.entry(form.clone()).or_insert(0);
+                        *total = total.saturating_add(count);
+                        if *total > 1 {
+                            return Some(form);
+                        }
+                        continue;
+                    }
+                };
+                let alternatives = rules.get(form[idx].as_nonterminal().unwrap());
+                for alt in alternatives.into_iter().flat_map(|x| x.iter()) {
+                    let new_form: Vec<Symbol> = form[..idx]
+                        .iter()
+                        .chain(alt)
+                        .chain(&form[idx + 1..])
+                        .cloned()
+                        .collect();
+                    // every Nonterminal is generating after the useless rules are removed
+                    let min_len: usize = new_form
+                        .iter()
+                        .map(|x| x.as_nonterminal().map_or(1, |n| shortest[n]))
+                        .sum();
+                    if min_len <= max_len {
+                        let total = next_forms.entry(new_form).or_insert(0u64);
+                        *total = total.saturating_add(count);
+                    }
+                }
+            }
+            forms = next_forms;
+        }
+        None
+    }
 
-        let test_rules = "
-            E -> T | E+T
-            F -> I | (E)
-            I -> a | b | Ia | Ib | I0 | I1
-            T -> F | T*F
-        ";
-        let expected = format!(
-            "{}\n",
-            join(
-                vec![
-                    "E -> (E) | E+T | I0 | I1 | Ia | Ib | T*F | a | b",
-                    "F -> (E) | I0 | I1 | Ia | Ib | a | b",
-                    "I -> I0 | I1 | Ia | Ib | a | b",
-                    "T -> (E) | I0 | I1 | Ia | Ib | T*F | a | b",
-                ],
-                "\n"
-            )
-        );
-        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
-        assert_eq!(format!("{}", cfg.remove_unit_rules()), expected);
+    /// The number of the parse trees (the distinct leftmost derivations) of the word,
+    /// saturated at u64::MAX. The derivations that return to the same Nonterminal
+    /// over the same part of the word (the unit and ε cycles) are not counted
+    pub fn num_derivations(&self, word: &str) -> u64 {
+        let rules = self.alternatives_map();
+        let mut counter = DerivationCounter {
+            rules: &rules,
+            word: word.chars().collect(),
+            memo: HashMap::new(),
+            active: HashSet::new(),
+        };
+        let len = counter.word.len();
+        counter.trees(&self.start, 0, len).0
     }
 
-    #[test]
-    fn remove_useless() {
-        let test_rules = "
-            S -> aAB | E
-            A -> aA | bB
-            B -> ACb| b
-            C -> A | bA | cC | aE
-            D -> a | c | Fb
-            E -> cE | aE | Eb | ED | FG
-            F -> BC | EC | AC
-            G -> Ga | Gb
-        ";
-        let expected = format!(
-            "{}\n",
-            join(
-                vec![
-                    "S -> aAB",
-                    "A -> aA | bB",
-                    "B -> ACb | b",
-                    "C -> A | bA | cC",
-                    "D -> Fb | a | c",
-                    "F -> AC | BC",
-                ],
-                "\n"
-            )
-        );
-        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
-        assert_eq!(format!("{}", cfg.remove_useless_rules()), expected);
+    pub fn to_bnf(&self) -> String {
+        ebnf::to_bnf(self)
     }
 
-    #[test]
-    fn remove_unreachable() {
-        let test_rules = "
-            S -> aAB | E
-            A -> aA | bB
-            B -> ACb| b
-            C -> A | bA | cC | aE
-            D -> a | c | Fb
-            E -> cE | aE | Eb | ED | FG
-            F -> BC | EC | AC
+    /// Right recursion is written as the repetition and the empty alternative as the option
+    pub fn to_ebnf(&self) -> String {
+        ebnf::to_ebnf(self)
+    }
+
+    /// The rules with the aligned '->', the start first and then the others by name.
+    /// The alternatives that don't fit into PRETTY_WIDTH continue on the next line under '->'
+    pub fn to_pretty_string(&self) -> String {
+        let mut rules: Vec<(String, Vec<String>)> = Vec::new();
+        for (left, alternatives) in self.rules_by_lhs() {
+            let mut alternatives: Vec<String> = alternatives
+                .into_iter()
+                .map(|right| {
+                    if right.is_empty() {
+                        "ε".to_string()
+                    } else {
+                        escape_rhs(right)
+                    }
+                }).collect();
+            alternatives.sort();
+            let rule = (left.to_string(), alternatives);
+            if left == self.start {
+                rules.insert(0, rule);
+            } else {
+                rules.push(rule);
+            }
+        }
+        if rules.is_empty() {
+            return format!("{} -> \n", self.start);
+        }
+        let width = rules.iter().map(|x| x.0.chars().count()).max().unwrap_or(0);
+        let mut out = String::new();
+        for (left, alternatives) in rules {
+            let mut line = format!("{:width$} ->", left, width = width);
+            for (idx, right) in alternatives.iter().enumerate() {
+                let piece = if idx == 0 {
+                    format!(" {}", right)
+                } else {
+                    format!(" | {}", right)
+                };
+                if idx > 0 && line.chars().count() + piece.chars().count() > PRETTY_WIDTH {
+                    out.push_str(&line);
+                    out.push('\n');
+                    line = format!("{:width$}{}", "", piece, width = width + 1);
+                } else {
+                    line.push_str(&piece);
+                }
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// One line per Nonterminal in the order the rules are read from the file
+    /// (or given to CfgBuilder and CFG::from_productions),
+    /// the comments are dropped and the rules of a Nonterminal are written together.
+    /// The rules added after reading (or all the rules of a transformed grammar)
+    /// follow in the order of Display
+    pub fn to_source(&self) -> String {
+        if self.productions.is_empty() {
+            return format!("{} -> \n", self.start);
+        }
+        let mut rest: Vec<&Production> = self
+            .productions
+            .iter()
+            .filter(|x| !self.source_order.contains(x))
+            .collect();
+        // the start rules come first as in Display, the sort is stable
+        rest.sort_by_key(|x| x.left != self.start);
+        let ordered = self
+            .source_order
+            .iter()
+            .filter(|x| self.productions.contains(x))
+            .chain(rest);
+        let mut lines: Vec<(&Nonterminal, Vec<String>)> = Vec::new();
+        for rule in ordered {
+            let right = if rule.right.is_empty() {
+                "ε".to_string()
+            } else {
+                escape_rhs(&rule.right)
+            };
+            match lines.iter().position(|x| x.0 == &rule.left) {
+                Some(idx) => lines[idx].1.push(right),
+                None => lines.push((&rule.left, vec![right])),
+            }
+        }
+        let mut out = String::new();
+        for (left, alternatives) in lines {
+            out.push_str(&format!("{} -> {}\n", left, join(alternatives, " | ")));
+        }
+        out
+    }
+
+    /// The grammar of the strings of the language accepted by the DFA (Bar-Hillel construction):
+    /// every Nonterminal 'A[p,q]' derives the strings of A that lead the DFA from p to q.
+    /// The useless and unreachable rules of the product are removed
+    pub fn intersect_regular(&self, dfa: &DFA) -> CFG {
+        let triple = |p: &State, nonterm: &Nonterminal, q: &State| {
+            let name = if nonterm.sub_index > 0 {
+                format!("{}{}", nonterm.name, nonterm.sub_index)
+            } else {
+                nonterm.name.clone()
+            };
+            Nonterminal::new(format!("{}[{},{}]", name, p.name, q.name), 0)
+        };
+        let states = dfa.states();
+        let mut productions = BTreeSet::new();
+        for rule in &self.productions {
+            for &from in &states {
+                // terminals move the DFA, Nonterminals can span between any pair of states
+                let mut stack = vec![(0, from, Vec::new())];
+                while let Some((pos, state, right)) = stack.pop() {
+                    if pos == rule.right.len() {
+                        let left = triple(from, &rule.left, state);
+                        productions.insert(Production::new(left, right));
+                        continue;
+                    }
+                    match &rule.right[pos] {
+                        &Symbol::T(ref t) => {
+                            if let Some(next) = dfa.next(state, t.symbol) {
+                                let mut right = right.clone();
+                                right.push(Symbol::T(t.clone()));
+                                stack.push((pos + 1, next, right));
+                            }
+                        }
+                        &Symbol::N(ref n) => {
+                            for &next in &states {
+                                let mut right = right.clone();
+                                right.push(Symbol::N(triple(state, n, next)));
+                                stack.push((pos + 1, next, right));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // the original Nonterminals are all renamed, so the start symbol is free
+        for &accept in states.iter().filter(|x| dfa.is_accepting(x)) {
+            let right = vec![Symbol::N(triple(dfa.start(), &self.start, accept))];
+            productions.insert(Production::new(self.start.clone(), right));
+        }
+        CFG::new(self.start.clone(), productions)
+            .remove_useless_rules()
+            .remove_unreachable_rules()
+    }
+
+    /// Graphviz digraph of the dependencies between Nonterminals,
+    /// every edge is labeled with the whole right-hand side of the production
+    pub fn to_dot(&self, show_terminals: bool) -> String {
+        let escape = |x: &str| x.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph CFG {\n");
+        for nonterm in self.nonterminals() {
+            let shape = if nonterm == self.start {
+                "doublecircle"
+            } else {
+                "circle"
+            };
+            let name = escape(&nonterm.to_string());
+            dot.push_str(&format!("    \"{}\" [shape={}];\n", name, shape));
+        }
+        if show_terminals {
+            for term in self.terminals() {
+                let name = escape(&term.to_string());
+                dot.push_str(&format!(
+                    "    \"'{}'\" [shape=box, label=\"{}\"];\n",
+                    name, name
+                ));
+            }
+        }
+        for rule in &self.productions {
+            let label = escape(&join(&rule.right, ""));
+            let mut targets: Vec<String> = Vec::new();
+            for sym in &rule.right {
+                let target = match sym {
+                    &Symbol::N(ref n) => escape(&n.to_string()),
+                    &Symbol::T(ref t) if show_terminals => format!("'{}'", escape(&t.to_string())),
+                    &Symbol::T(_) => continue,
+                };
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+            for target in targets {
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    escape(&rule.left.to_string()),
+                    target,
+                    label
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// CYK membership test over the Chomsky Normal Form of the grammar
+    pub fn accepts_cyk(&self, input: &str) -> bool {
+        CYKParser::new(self).accepts(input)
+    }
+
+    /// One of the parse trees of the input over the Chomsky Normal Form of the grammar
+    pub fn parse_cyk(&self, input: &str) -> Option<ParseTree> {
+        let cyk = CYKParser::new(self);
+        cyk.parse(input)
+            .and_then(|path| ParseTree::from_leftmost(&mut path.into_iter()))
+    }
+
+    /// The productions of a leftmost derivation of the target in the order they are applied
+    pub fn derive(&self, target: &str) -> Option<Vec<Production>> {
+        LeftmostSearch::new(self, target).derive(&self.start)
+    }
+
+    /// CFG::derive trying at most 'budget' productions, the search that gives up
+    /// is told from the target out of the language by Err(BudgetExceeded)
+    pub fn derive_with_budget(
+        &self,
+        target: &str,
+        budget: usize,
+    ) -> Result<Option<Vec<Production>>, BudgetExceeded> {
+        LeftmostSearch::new(self, target)
+            .with_budget(budget)
+            .try_derive(&self.start)
+    }
+
+    pub fn accepts_with_budget(&self, input: &str, budget: usize) -> Result<bool, BudgetExceeded> {
+        self.derive_with_budget(input, budget).map(|x| x.is_some())
+    }
+
+    /// Greibach Normal Form: every production is 'A -> aB1..Bn' or 'S -> ε'
+    pub fn to_gnf(&self) -> CFG {
+        let cfg = self.to_cnf().eliminate_left_recursion();
+        let mut rules = cfg.alternatives_map();
+
+        // without left recursion the substitution of the leading Nonterminals terminates
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let lefts: Vec<Nonterminal> = rules.keys().cloned().collect();
+            for left in lefts {
+                let mut alternatives = BTreeSet::new();
+                for alt in &rules[&left] {
+                    match alt.first() {
+                        Some(&Symbol::N(ref first)) => {
+                            changed = true;
+                            for sub in rules.get(first).into_iter().flat_map(|x| x.iter()) {
+                                alternatives.insert(sub.iter().chain(&alt[1..]).cloned().collect());
+                            }
+                        }
+                        _ => {
+                            alternatives.insert(alt.clone());
+                        }
+                    }
+                }
+                rules.insert(left, alternatives);
+            }
+        }
+        // the Nonterminals substituted at the leading positions may become unreachable
+        CFG::from_alternatives_map(cfg.start, rules).remove_unreachable_rules()
+    }
+
+    /// Removes both immediate and indirect left recursion,
+    /// a grammar without left recursion is returned as is
+    pub fn remove_left_recursion(&self) -> CFG {
+        if !self.is_left_recursive() {
+            return CFG::new(self.start.clone(), self.productions.clone());
+        }
+        self.remove_epsilon_rules()
+            .remove_unit_rules()
+            .eliminate_left_recursion()
+    }
+
+    /// Checks for derivations 'A =>+ Aα', including the ones through nullable prefixes
+    pub fn is_left_recursive(&self) -> bool {
+        let nullable = self.nullable_symbols();
+        let mut left_corners: HashMap<Nonterminal, HashSet<Nonterminal>> = HashMap::new();
+        for rule in &self.productions {
+            let corners = left_corners
+                .entry(rule.left.clone())
+                .or_insert_with(HashSet::new);
+            for sym in &rule.right {
+                match sym.as_nonterminal() {
+                    Some(nonterm) => {
+                        corners.insert(nonterm.clone());
+                        if !nullable.contains(nonterm) {
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let lefts: Vec<Nonterminal> = left_corners.keys().cloned().collect();
+            for left in lefts {
+                let mut reachable = left_corners[&left].clone();
+                for corner in &left_corners[&left] {
+                    if let Some(next) = left_corners.get(corner) {
+                        reachable.extend(next.iter().cloned());
+                    }
+                }
+                if reachable.contains(&left) {
+                    return true;
+                }
+                if reachable.len() != left_corners[&left].len() {
+                    changed = true;
+                    left_corners.insert(left, reachable);
+                }
+            }
+        }
+        false
+    }
+
+    /// Some Nonterminal derives 'αAβ' with both α and β deriving non-empty strings.
+    /// A grammar that is not self-embedding has a regular language, the converse is not true:
+    /// 'S -> aS | Sb | c' is self-embedding. The search runs over the simplified grammar,
+    /// where every symbol derives a non-empty string, by the states (A, α ≠ ε, β ≠ ε)
+    pub fn is_self_embedding(&self) -> bool {
+        let cfg = self.simplify();
+        let rules = cfg.alternatives_map();
+        for root in rules.keys() {
+            let mut visited: HashSet<(&Nonterminal, bool, bool)> = HashSet::new();
+            let mut stack = vec![(root, false, false)];
+            while let Some((nonterm, left, right)) = stack.pop() {
+                for right_side in rules.get(nonterm).into_iter().flat_map(|x| x.iter()) {
+                    for (idx, sym) in right_side.iter().enumerate() {
+                        let next = match sym.as_nonterminal() {
+                            Some(next) => next,
+                            None => continue,
+                        };
+                        let state = (next, left || idx > 0, right || idx + 1 < right_side.len());
+                        if state == (root, true, true) {
+                            return true;
+                        }
+                        if visited.insert(state) {
+                            stack.push(state);
+                        }
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// The tail recursion 'A -> wA': the Nonterminal is the last symbol of one of its rules.
+    /// CFG::to_ebnf writes such rules as the repetition '{ w }'
+    pub fn is_right_recursive(&self, nonterm: &Nonterminal) -> bool {
+        self.alternatives(nonterm)
+            .any(|right| right.last().map_or(false, |x| x.is_eq_nonterm(nonterm)))
+    }
+
+    /// Paull's algorithm, expects a grammar without cycles 'A =>+ A'
+    /// and without epsilon rules except for the start, e.g. in Chomsky Normal Form
+    pub fn eliminate_left_recursion(&self) -> CFG {
+        let mut taken = self.nonterminals();
+        let mut rules = self.alternatives_map();
+        let mut order = vec![self.start.clone()];
+        order.extend(rules.keys().filter(|&x| x != &self.start).cloned());
+
+        for idx in 0..order.len() {
+            let current = &order[idx];
+            let mut alternatives = rules.remove(current).unwrap_or_default();
+            // substitute 'Ai -> Aj..' where j < i
+            for prev in &order[..idx] {
+                let mut substituted = BTreeSet::new();
+                for alt in alternatives {
+                    if alt.first().and_then(|x| x.as_nonterminal()) == Some(prev) {
+                        for sub in rules.get(prev).into_iter().flat_map(|x| x.iter()) {
+                            substituted.insert(sub.iter().chain(&alt[1..]).cloned().collect());
+                        }
+                    } else {
+                        substituted.insert(alt);
+                    }
+                }
+                alternatives = substituted;
+            }
+
+            // immediate left recursion 'A -> Aα | β' becomes 'A -> β | βA1', 'A1 -> α | αA1'
+            let (recursive, other): (Vec<_>, Vec<_>) = alternatives
+                .into_iter()
+                .partition(|alt| alt.first().and_then(|x| x.as_nonterminal()) == Some(current));
+            let recursive: Vec<Vec<Symbol>> = recursive
+                .into_iter()
+                .map(|alt| alt[1..].to_vec())
+                .filter(|alt| !alt.is_empty())
+                .collect();
+            if recursive.is_empty() {
+                rules.insert(current.clone(), other.into_iter().collect());
+                continue;
+            }
+            let tail = current.fresh(&mut taken);
+            let mut alternatives = BTreeSet::new();
+            for alt in other {
+                let mut with_tail = alt.clone();
+                with_tail.push(Symbol::N(tail.clone()));
+                alternatives.insert(alt);
+                alternatives.insert(with_tail);
+            }
+            let mut tail_alternatives = BTreeSet::new();
+            for alt in recursive {
+                let mut with_tail = alt.clone();
+                with_tail.push(Symbol::N(tail.clone()));
+                tail_alternatives.insert(alt);
+                tail_alternatives.insert(with_tail);
+            }
+            rules.insert(current.clone(), alternatives);
+            rules.insert(tail, tail_alternatives);
+        }
+        CFG::from_alternatives_map(self.start.clone(), rules)
+    }
+
+    /// The alternatives with the same first symbol share their longest common prefix:
+    /// 'A -> ab | ac' becomes 'A -> aA1', 'A1 -> b | c'.
+    /// The new Nonterminals are factored again until no two alternatives start the same way
+    pub fn left_factor(&self) -> CFG {
+        let mut taken = self.nonterminals();
+        let mut rules = self.alternatives_map();
+        let mut pending: Vec<Nonterminal> = rules.keys().cloned().collect();
+        while let Some(current) = pending.pop() {
+            let mut groups: BTreeMap<Option<Symbol>, Vec<Vec<Symbol>>> = BTreeMap::new();
+            for alt in rules.remove(&current).unwrap_or_default() {
+                groups
+                    .entry(alt.first().cloned())
+                    .or_insert_with(Vec::new)
+                    .push(alt);
+            }
+            let mut alternatives = BTreeSet::new();
+            for (first, group) in groups {
+                if first.is_none() || group.len() < 2 {
+                    alternatives.extend(group);
+                    continue;
+                }
+                let mut prefix = 1;
+                while group
+                    .iter()
+                    .all(|alt| alt.len() > prefix && alt[prefix] == group[0][prefix])
+                {
+                    prefix += 1;
+                }
+                let tail = current.fresh(&mut taken);
+                let mut factored = group[0][..prefix].to_vec();
+                factored.push(Symbol::N(tail.clone()));
+                alternatives.insert(factored);
+                let tail_alternatives = group.iter().map(|alt| alt[prefix..].to_vec()).collect();
+                rules.insert(tail.clone(), tail_alternatives);
+                pending.push(tail);
+            }
+            rules.insert(current, alternatives);
+        }
+        CFG::from_alternatives_map(self.start.clone(), rules)
+    }
+
+    /// The right sides of the rules of the Nonterminal
+    pub fn alternatives<'a>(
+        &'a self,
+        nonterm: &'a Nonterminal,
+    ) -> impl Iterator<Item = &'a Vec<Symbol>> + 'a {
+        self.productions
+            .iter()
+            .filter(move |x| &x.left == nonterm)
+            .map(|x| &x.right)
+    }
+
+    /// The right sides of the rules grouped by their left sides
+    pub fn rules_by_lhs(&self) -> BTreeMap<Nonterminal, Vec<&Vec<Symbol>>> {
+        let mut rules = BTreeMap::new();
+        for rule in &self.productions {
+            rules
+                .entry(rule.left.clone())
+                .or_insert_with(Vec::new)
+                .push(&rule.right);
+        }
+        rules
+    }
+
+    fn alternatives_map(&self) -> BTreeMap<Nonterminal, BTreeSet<Vec<Symbol>>> {
+        let mut rules = BTreeMap::new();
+        for rule in &self.productions {
+            rules
+                .entry(rule.left.clone())
+                .or_insert_with(BTreeSet::new)
+                .insert(rule.right.clone());
+        }
+        rules
+    }
+
+    /// Renames the Nonterminal-s apart from the taken ones, the new names are marked as taken
+    fn rename_apart(&self, taken: &mut BTreeSet<Nonterminal>) -> CFG {
+        let mut names = self.nonterminals();
+        names.insert(self.start.clone());
+        let renames: HashMap<Nonterminal, Nonterminal> = names
+            .into_iter()
+            .map(|x| {
+                let new_name = x.fresh(taken);
+                (x, new_name)
+            })
+            .collect();
+        self.rename_with(|x| renames[x].clone())
+    }
+
+    fn rename_with<F: Fn(&Nonterminal) -> Nonterminal>(&self, rename: F) -> CFG {
+        let productions = self
+            .productions
+            .iter()
+            .map(|rule| {
+                let right = rule
+                    .right
+                    .iter()
+                    .map(|x| match x {
+                        &Symbol::N(ref n) => Symbol::N(rename(n)),
+                        &Symbol::T(_) => x.clone(),
+                    })
+                    .collect();
+                Production::new(rename(&rule.left), right)
+            })
+            .collect();
+        CFG::new(rename(&self.start), productions)
+    }
+
+    /// Renames the Nonterminal everywhere in the grammar,
+    /// fails if the new name is already in use to not merge two Nonterminal-s by accident
+    pub fn rename_nonterminal(
+        &mut self,
+        from: &Nonterminal,
+        to: Nonterminal,
+    ) -> Result<(), CfgError> {
+        if from == &to {
+            return Ok(());
+        }
+        if self.start == to || self.nonterminals().contains(&to) {
+            return Err(CfgError::NonterminalExists(to));
+        }
+        *self = self.rename_with(|x| if x == from { to.clone() } else { x.clone() });
+        Ok(())
+    }
+
+    /// A grammar of L(self) ∪ L(other): 'S0 -> S1 | S2' where S1 and S2 are the start symbols,
+    /// the Nonterminal-s of other are renamed if they clash with the ones of self
+    pub fn union(&self, other: &CFG) -> CFG {
+        let mut taken = self.nonterminals();
+        taken.insert(self.start.clone());
+        let other = other.rename_apart(&mut taken);
+        let start = self.start.fresh(&mut taken);
+        let mut productions = self.productions.clone();
+        productions.extend(other.productions);
+        productions.insert(Production::new(start.clone(), vec![Symbol::N(self.start.clone())]));
+        productions.insert(Production::new(start.clone(), vec![Symbol::N(other.start)]));
+        CFG::new(start, productions)
+    }
+
+    /// Adds the rules of the other part of the same grammar, the start symbol of self is kept.
+    /// Unlike CFG::union the Nonterminal-s are not renamed, the equal names are the same symbol
+    pub fn merge(&mut self, other: CFG) {
+        for rule in other.source_order {
+            if !self.productions.contains(&rule) {
+                self.source_order.push(rule);
+            }
+        }
+        self.productions.extend(other.productions);
+    }
+
+    /// A grammar of L(self)·L(other): 'S0 -> S1S2' where S1 and S2 are the start symbols,
+    /// the Nonterminal-s of other are renamed if they clash with the ones of self
+    pub fn concat(&self, other: &CFG) -> CFG {
+        let mut taken = self.nonterminals();
+        taken.insert(self.start.clone());
+        let other = other.rename_apart(&mut taken);
+        let start = self.start.fresh(&mut taken);
+        let mut productions = self.productions.clone();
+        productions.extend(other.productions);
+        productions.insert(Production::new(
+            start.clone(),
+            vec![Symbol::N(self.start.clone()), Symbol::N(other.start)],
+        ));
+        CFG::new(start, productions)
+    }
+
+    /// A grammar of L(self)*: 'S0 -> S0S | ε' where S is the start symbol
+    pub fn star(&self) -> CFG {
+        let mut taken = self.nonterminals();
+        taken.insert(self.start.clone());
+        let start = self.start.fresh(&mut taken);
+        let mut productions = self.productions.clone();
+        productions.insert(Production::new(
+            start.clone(),
+            vec![Symbol::N(start.clone()), Symbol::N(self.start.clone())],
+        ));
+        productions.insert(Production::new(start.clone(), vec![]));
+        CFG::new(start, productions)
+    }
+
+    /// A grammar of the reversed strings of L(self)
+    pub fn reverse(&self) -> CFG {
+        let productions = self
+            .productions
+            .iter()
+            .map(|rule| {
+                let right = rule.right.iter().rev().cloned().collect();
+                Production::new(rule.left.clone(), right)
+            })
+            .collect();
+        CFG::new(self.start.clone(), productions)
+    }
+
+    /// The image of the language under the homomorphism: every terminal is replaced
+    /// by the string of terminals it is mapped to, the empty string erases the terminal
+    pub fn map_terminals<F: Fn(char) -> Vec<char>>(&self, f: F) -> CFG {
+        let productions = self
+            .productions
+            .iter()
+            .map(|rule| {
+                let mut right = Vec::new();
+                for sym in &rule.right {
+                    match sym {
+                        &Symbol::T(ref t) => right.extend(
+                            f(t.symbol).into_iter().map(|x| Symbol::T(Terminal::new(x))),
+                        ),
+                        &Symbol::N(_) => right.push(sym.clone()),
+                    }
+                }
+                Production::new(rule.left.clone(), right)
+            })
+            .collect();
+        CFG::new(self.start.clone(), productions)
+    }
+
+    pub fn productions_count(&self) -> usize {
+        self.productions.len()
+    }
+
+    /// The number of distinct strings of length n in the language.
+    /// The strings are enumerated over the Chomsky Normal Form of the grammar,
+    /// so the time and memory grow with the count and it is practical for small n only
+    pub fn count_strings_of_length(&self, n: usize) -> u64 {
+        let cnf = self.to_cnf();
+        match Generator::new(cnf, n as u32, n as u32, true) {
+            Ok(generator) => generator
+                .unique()
+                .filter(|x| x.len() == n) // the empty string is generated regardless of the limits
+                .count() as u64,
+            Err(GeneratorError::EmptyLanguage) => 0,
+        }
+    }
+
+    /// All the distinct strings of the language not longer than max_len.
+    /// They are generated over the simplified grammar: without epsilon and unit rules
+    /// a string of length n is derived in at most 2n - 1 steps, that bounds the search
+    pub fn enumerate(&self, max_len: usize) -> HashSet<Vec<Symbol>> {
+        match Generator::new(self.simplify(), 0, max_len as u32, true) {
+            Ok(generator) => generator.max_steps(2 * max_len + 1).collect(),
+            Err(GeneratorError::EmptyLanguage) => HashSet::new(),
+        }
+    }
+
+    /// One of the shortest strings of the language or None if the language is empty
+    pub fn shortest_string(&self) -> Option<Vec<Symbol>> {
+        self.shortest_strings().remove(&self.start)
+    }
+
+    /// A shortest terminal string for every Nonterminal of the grammar,
+    /// None for the Nonterminals that do not derive any terminal string
+    pub fn witnesses(&self) -> HashMap<Nonterminal, Option<Vec<Symbol>>> {
+        let mut shortest = self.shortest_strings();
+        self.nonterminals()
+            .into_iter()
+            .map(|x| {
+                let witness = shortest.remove(&x);
+                (x, witness)
+            }).collect()
+    }
+
+    /// The shortest terminal string derivable from each generating Nonterminal,
+    /// lengths only decrease, so the fixpoint is reached
+    fn shortest_strings(&self) -> HashMap<Nonterminal, Vec<Symbol>> {
+        let mut shortest: HashMap<Nonterminal, Vec<Symbol>> = HashMap::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &self.productions {
+                let mut candidate = Vec::new();
+                let mut complete = true;
+                for sym in &rule.right {
+                    match sym {
+                        &Symbol::T(_) => candidate.push(sym.clone()),
+                        &Symbol::N(ref n) => match shortest.get(n) {
+                            Some(word) => candidate.extend(word.iter().cloned()),
+                            None => {
+                                complete = false;
+                                break;
+                            }
+                        },
+                    }
+                }
+                if !complete {
+                    continue;
+                }
+                let better = shortest
+                    .get(&rule.left)
+                    .map_or(true, |x| candidate.len() < x.len());
+                if better {
+                    shortest.insert(rule.left.clone(), candidate);
+                    changed = true;
+                }
+            }
+        }
+        shortest
+    }
+
+    fn from_alternatives_map(
+        start: Nonterminal,
+        rules: BTreeMap<Nonterminal, BTreeSet<Vec<Symbol>>>,
+    ) -> CFG {
+        let mut productions = BTreeSet::new();
+        for (left, alternatives) in rules {
+            for alt in alternatives {
+                productions.insert(Production::new(left.clone(), alt));
+            }
+        }
+        CFG::new(start, productions)
+    }
+}
+
+/// The rules with the Nonterminal-s replaced by numbers,
+/// the fixpoint loops run over them without cloning and hashing the names
+struct InternedRules {
+    names: Vec<Nonterminal>,
+    rules: Vec<InternedRule>,
+}
+
+struct InternedRule {
+    left: usize,
+    right: Vec<usize>,
+    has_terminal: bool,
+}
+
+impl InternedRules {
+    fn new(grammar: &CFG) -> InternedRules {
+        let mut ids: HashMap<&Nonterminal, usize> = HashMap::new();
+        let mut names = Vec::new();
+        let mut rules = Vec::with_capacity(grammar.productions.len());
+        for rule in &grammar.productions {
+            let left = InternedRules::intern(&mut ids, &mut names, &rule.left);
+            let mut right = Vec::new();
+            let mut has_terminal = false;
+            for sym in &rule.right {
+                match sym {
+                    &Symbol::N(ref n) => {
+                        right.push(InternedRules::intern(&mut ids, &mut names, n))
+                    }
+                    &Symbol::T(_) => has_terminal = true,
+                }
+            }
+            rules.push(InternedRule {
+                left: left,
+                right: right,
+                has_terminal: has_terminal,
+            });
+        }
+        InternedRules {
+            names: names,
+            rules: rules,
+        }
+    }
+
+    fn intern<'a>(
+        ids: &mut HashMap<&'a Nonterminal, usize>,
+        names: &mut Vec<Nonterminal>,
+        name: &'a Nonterminal,
+    ) -> usize {
+        if let Some(&id) = ids.get(name) {
+            return id;
+        }
+        ids.insert(name, names.len());
+        names.push(name.clone());
+        names.len() - 1
+    }
+
+    /// The left sides of the rules accepted by the filter
+    /// whose right side Nonterminal-s are all in the set already
+    fn fixpoint<F: Fn(&InternedRule) -> bool>(&self, filter: F) -> HashSet<Nonterminal> {
+        let mut marked = vec![false; self.names.len()];
+        let rules: Vec<&InternedRule> = self.rules.iter().filter(|x| filter(x)).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &rules {
+                if !marked[rule.left] && rule.right.iter().all(|&x| marked[x]) {
+                    marked[rule.left] = true;
+                    changed = true;
+                }
+            }
+        }
+        self.names
+            .iter()
+            .zip(marked)
+            .filter(|x| x.1)
+            .map(|x| x.0.clone())
+            .collect()
+    }
+}
+
+/// Counts the parse trees of the spans of the word, the spans of the Nonterminal-s are memoized.
+/// The counts cut by a cycle depend on the active spans and are not memoized
+struct DerivationCounter<'a> {
+    rules: &'a BTreeMap<Nonterminal, BTreeSet<Vec<Symbol>>>,
+    word: Vec<char>,
+    memo: HashMap<(&'a Nonterminal, usize, usize), u64>,
+    active: HashSet<(&'a Nonterminal, usize, usize)>,
+}
+
+impl<'a> DerivationCounter<'a> {
+    /// The trees of the Nonterminal over word[from..to] and whether a cycle was cut
+    fn trees(&mut self, nonterm: &Nonterminal, from: usize, to: usize) -> (u64, bool) {
+        let (nonterm, alternatives) = match self.rules.get_key_value(nonterm) {
+            Some(rule) => rule,
+            None => return (0, false),
+        };
+        let key = (nonterm, from, to);
+        if let Some(&count) = self.memo.get(&key) {
+            return (count, false);
+        }
+        if self.active.contains(&key) {
+            return (0, true);
+        }
+        self.active.insert(key);
+        let mut total = 0u64;
+        let mut cut = false;
+        for right in alternatives {
+            let (count, cycle) = self.sequence(right, from, to);
+            total = total.saturating_add(count);
+            cut |= cycle;
+        }
+        self.active.remove(&key);
+        if !cut {
+            self.memo.insert(key, total);
+        }
+        (total, cut)
+    }
+
+    /// The ways the symbols derive word[from..to]
+    fn sequence(&mut self, symbols: &'a [Symbol], from: usize, to: usize) -> (u64, bool) {
+        let (first, rest) = match symbols.split_first() {
+            Some(x) => x,
+            None => return (if from == to { 1 } else { 0 }, false),
+        };
+        match first {
+            &Symbol::T(ref t) => {
+                if from < to && self.word[from] == t.symbol {
+                    self.sequence(rest, from + 1, to)
+                } else {
+                    (0, false)
+                }
+            }
+            &Symbol::N(ref n) => {
+                let mut total = 0u64;
+                let mut cut = false;
+                for mid in from..to + 1 {
+                    let (head, head_cut) = self.trees(n, from, mid);
+                    cut |= head_cut;
+                    if head == 0 {
+                        continue;
+                    }
+                    let (tail, tail_cut) = self.sequence(rest, mid, to);
+                    cut |= tail_cut;
+                    total = total.saturating_add(head.saturating_mul(tail));
+                }
+                (total, cut)
+            }
+        }
+    }
+}
+
+/// Builds a CFG in code, the rules are written the same way as in a grammar file:
+///
+/// CfgBuilder::new().rule("S", "aSb").rule("S", "").build()
+#[derive(Debug, Default)]
+pub struct CfgBuilder {
+    start: Option<String>,
+    rules: Vec<String>,
+}
+
+impl CfgBuilder {
+    pub fn new() -> CfgBuilder {
+        CfgBuilder::default()
+    }
+
+    /// The start symbol, by default it is the left side of the first rule
+    pub fn start(mut self, start: &str) -> CfgBuilder {
+        self.start = Some(start.to_string());
+        self
+    }
+
+    pub fn rule(mut self, left: &str, right: &str) -> CfgBuilder {
+        self.rules.push(format!("{} -> {}", left, right));
+        self
+    }
+
+    pub fn build(self) -> Result<CFG, CfgError> {
+        let mut productions = Vec::new();
+        for (idx, rule) in self.rules.iter().enumerate() {
+            let add_productions =
+                CFG::parse_production(rule, false).map_err(|e| e.at_line(idx + 1))?;
+            productions.extend(add_productions);
+        }
+        let start = match self.start {
+            Some(start) => match Symbol::new(start.clone()) {
+                Symbol::N(n) => n,
+                Symbol::T(_) => {
+                    return Err(CfgError::TerminalOnLhs {
+                        line: 0,
+                        text: start,
+                    })
+                }
+            },
+            None => match self.rules.first() {
+                Some(rule) => CFG::parse_production(rule, false)?[0].left.clone(),
+                None => return Err(CfgError::NoRules),
+            },
+        };
+        Ok(CFG::from_productions(start, productions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use self::super::*;
+    use generator::Generator;
+    use serde_yaml;
+    use std::io::Cursor;
+
+    #[test]
+    fn load_cfg() {
+        let productions = vec![
+            Production::new(
+                Nonterminal::new("S".to_string(), 2),
+                vec![
+                    Symbol::N(Nonterminal::new("S".to_string(), 1)),
+                    Symbol::N(Nonterminal::new("Some".to_string(), 0)),
+                    Symbol::T(Terminal { symbol: 'a' }),
+                ],
+            ),
+            Production::new(
+                Nonterminal::new("S".to_string(), 2),
+                vec![
+                    Symbol::N(Nonterminal::new("s".to_string(), 0)),
+                    Symbol::N(Nonterminal::new("S".to_string(), 0)),
+                    Symbol::T(Terminal { symbol: 'a' }),
+                ],
+            ),
+        ];
+        let expected = CFG::new(
+            productions[0].left.clone(),
+            productions.into_iter().collect(),
+        );
+        let test_definition = "<S2> -> <S1><Some>a | <s>Sa\n";
+        let cfg = CFG::load_from_reader(Cursor::new(test_definition)).unwrap();
+        assert_eq!(cfg.start, expected.start);
+        assert_eq!(cfg.productions, expected.productions);
+        assert_eq!(format!("{}", cfg), test_definition);
+        let text = Cursor::new("<a> -> ||||");
+        assert!(CFG::load_from_reader(text).is_ok());
+    }
+
+    #[test]
+    fn load_mailformed_cfg() {
+        let text = Cursor::new("S -> <");
+        assert!(CFG::load_from_reader(text).is_err(), "Eat unexpected '<'");
+        let text = Cursor::new("S -> <<a>");
+        assert!(CFG::load_from_reader(text).is_err(), "Eat unexpected '<'");
+        let text = Cursor::new("S -> >");
+        assert!(CFG::load_from_reader(text).is_err(), "Eat unexpected '>'");
+        let text = Cursor::new("S -> <a>>");
+        assert!(CFG::load_from_reader(text).is_err(), "Eat unexpected '>'");
+        let text = Cursor::new(" -> <a>");
+        assert!(CFG::load_from_reader(text).is_err(), "Missing left Symbol");
+        let text = Cursor::new("a -> ");
+        assert!(CFG::load_from_reader(text).is_err(), "Terminal at LHS");
+
+        match CFG::load_from_str("S -> a\n  a -> b") {
+            Err(CfgError::TerminalOnLhs { line, text }) => {
+                assert_eq!(line, 2);
+                assert_eq!(text, "a -> b");
+            }
+            x => panic!("Unexpected result: {:?}", x),
+        }
+        match CFG::load_from_str("S -> <a") {
+            Err(CfgError::BadRule { line: 1, .. }) => {}
+            x => panic!("Unexpected result: {:?}", x),
+        }
+        match CFG::load_from_str("S -> a\nAB -> c") {
+            Err(e @ CfgError::MultiSymbolLhs { .. }) => {
+                assert_eq!(e.line(), Some(2));
+                assert_eq!(e.to_string(), "Several symbols at LHS (A, B) on line 2: AB -> c");
+            }
+            x => panic!("Unexpected result: {:?}", x),
+        }
+        match CFG::load_from_str("a<B> -> c") {
+            Err(CfgError::MultiSymbolLhs { symbols, .. }) => {
+                assert_eq!(join(symbols, ""), "aB");
+            }
+            x => panic!("Unexpected result: {:?}", x),
+        }
+        assert!(CFG::load_from_str("<AB> -> c").is_ok());
+        match CFG::load_from_str("# comment only") {
+            Err(CfgError::NoRules) => {}
+            x => panic!("Unexpected result: {:?}", x),
+        }
+        assert!(match CFG::load("/nonexistent/grammar.txt") {
+            Err(CfgError::Io(_)) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn remove_epsilon() {
+        let test_rules = r#"
+            S -> AaB | aB | cC
+            A -> AB | a | b | B
+            B -> Ba |
+            C -> AB | c
+        "#;
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "S -> Aa | AaB | a | aB | c | cC",
+                    "A -> AB | B | a | b",
+                    "B -> Ba | a",
+                    "C -> A | AB | B | c",
+                ],
+                "\n"
+            )
+        );
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert_eq!(format!("{}", cfg.remove_epsilon_rules()), expected);
+    }
+
+    #[test]
+    fn remove_units() {
+        let test_rules = "
+            Я -> AaB | aB | cC
+            A -> AB | a | b | B
+            B -> Ba |
+            C -> AB | c
+        ";
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "Я -> Aa | AaB | a | aB | c | cC",
+                    "A -> AB | Ba | a | b",
+                    "B -> Ba | a",
+                    "C -> AB | Ba | a | b | c",
+                ],
+                "\n"
+            )
+        );
+
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules))
+            .unwrap()
+            .remove_epsilon_rules();
+        assert_eq!(format!("{}", cfg.remove_unit_rules()), expected);
+
+        let test_rules = "
+            E -> T | E+T
+            F -> I | (E)
+            I -> a | b | Ia | Ib | I0 | I1
+            T -> F | T*F
+        ";
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "E -> (E) | E+T | I0 | I1 | Ia | Ib | T*F | a | b",
+                    "F -> (E) | I0 | I1 | Ia | Ib | a | b",
+                    "I -> I0 | I1 | Ia | Ib | a | b",
+                    "T -> (E) | I0 | I1 | Ia | Ib | T*F | a | b",
+                ],
+                "\n"
+            )
+        );
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert_eq!(format!("{}", cfg.remove_unit_rules()), expected);
+
+        // the unit cycles are collapsed
+        let test_rules = "
+            A -> B | a
+            B -> A | C
+            C -> C | cA
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let removed = cfg.remove_unit_rules();
+        let expected = "A -> a | cA\nB -> a | cA\nC -> cA\n";
+        assert_eq!(format!("{}", removed), expected);
+        assert!(removed
+            .productions
+            .iter()
+            .all(|x| x.right.len() != 1 || x.right[0].is_terminal()));
+        for input in vec!["a", "ca", "ccca"] {
+            assert!(cfg.accepts_cyk(input) && removed.accepts_cyk(input), "{}", input);
+        }
+        assert!(!removed.accepts_cyk("c"));
+    }
+
+    #[test]
+    fn remove_useless() {
+        let test_rules = "
+            S -> aAB | E
+            A -> aA | bB
+            B -> ACb| b
+            C -> A | bA | cC | aE
+            D -> a | c | Fb
+            E -> cE | aE | Eb | ED | FG
+            F -> BC | EC | AC
+            G -> Ga | Gb
+        ";
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "S -> aAB",
+                    "A -> aA | bB",
+                    "B -> ACb | b",
+                    "C -> A | bA | cC",
+                    "D -> Fb | a | c",
+                    "F -> AC | BC",
+                ],
+                "\n"
+            )
+        );
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert_eq!(format!("{}", cfg.remove_useless_rules()), expected);
+    }
+
+    #[test]
+    fn remove_unreachable() {
+        let test_rules = "
+            S -> aAB | E
+            A -> aA | bB
+            B -> ACb| b
+            C -> A | bA | cC | aE
+            D -> a | c | Fb
+            E -> cE | aE | Eb | ED | FG
+            F -> BC | EC | AC
             G -> Ga | Gb
         ";
-        let expected = format!(
-            "{}\n",
-            join(
-                vec![
-                    "S -> aAB",
-                    "A -> aA | bB",
-                    "B -> ACb | b",
-                    "C -> A | bA | cC",
-                ],
-                "\n"
-            )
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "S -> aAB",
+                    "A -> aA | bB",
+                    "B -> ACb | b",
+                    "C -> A | bA | cC",
+                ],
+                "\n"
+            )
+        );
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules))
+            .unwrap()
+            .remove_useless_rules();
+        assert_eq!(format!("{}", cfg.remove_unreachable_rules()), expected);
+    }
+
+    #[test]
+    fn simplify() {
+        let test_rules = "
+            S ->  | S(S)S
+        ";
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "<S1> ->  | () | ()S | (S) | (S)S | S() | S()S | S(S) | S(S)S",
+                    "S -> () | ()S | (S) | (S)S | S() | S()S | S(S) | S(S)S",
+                ],
+                "\n"
+            )
+        );
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert_eq!(format!("{}", cfg.simplify()), expected);
+    }
+
+    #[test]
+    fn chomsky() {
+        let test_rules = "
+            A ->  BAB | B |
+            B -> 00 |
+        ";
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "<A1> ->  | <0><0> | AB | B<AB> | BA | BB",
+                    "<0> -> 0",
+                    "A -> <0><0> | AB | B<AB> | BA | BB",
+                    "<AB> -> AB",
+                    "B -> <0><0>",
+                ],
+                "\n"
+            )
+        );
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert_eq!(format!("{}", cfg.chomsky()), expected);
+    }
+
+    #[test]
+    fn to_cnf() {
+        let test_rules = "
+            S -> aSb | ab
+        ";
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "<S1> -> <a><S2> | <a><b>",
+                    "S -> <a><S2> | <a><b>",
+                    "<S2> -> S<b>",
+                    "<a> -> a",
+                    "<b> -> b",
+                ],
+                "\n"
+            )
+        );
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert_eq!(format!("{}", cfg.to_cnf()), expected);
+
+        let test_rules = "
+            A ->  aBCD | B |
+            B -> 00 |
+            C -> c
+            D -> d
+        ";
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules))
+            .unwrap()
+            .to_cnf();
+        assert!(cfg.is_in_cnf());
+        for rule in &cfg.productions {
+            match rule.right.len() {
+                0 => assert_eq!(rule.left, cfg.start),
+                1 => assert!(rule.right[0].is_terminal()),
+                2 => assert!(rule.right.iter().all(|x| x.is_nonterminal())),
+                _ => panic!("Not in CNF: {:?}", rule),
+            }
+        }
+    }
+
+    #[test]
+    fn to_gnf() {
+        let test_rules = "
+            S -> SA | a
+            A -> b
+        ";
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        let gnf = cfg.to_gnf();
+        for rule in &gnf.productions {
+            if rule.right.is_empty() {
+                assert_eq!(rule.left, gnf.start);
+                continue;
+            }
+            assert!(rule.right[0].is_terminal(), "Not in GNF: {:?}", rule);
+            assert!(rule.right[1..].iter().all(|x| x.is_nonterminal()));
+        }
+        let expected: HashSet<Vec<Symbol>> = Generator::new(cfg, 0, 6, true).unwrap().collect();
+        let generated: HashSet<Vec<Symbol>> = Generator::new(gnf, 0, 6, true).unwrap().collect();
+        assert_eq!(generated, expected);
+    }
+
+    #[test]
+    fn is_self_embedding() {
+        for (rules, expected) in vec![
+            ("S -> aSb | ab", true),
+            ("S -> aS | a", false),
+            ("S -> aA | b\nA -> Sb", true),
+            ("S -> aS | Sb | c", true),
+            ("S -> AS | a\nA -> ε", false),
+            ("S -> ASB | a\nA -> ε | a\nB -> b", true),
+            ("S -> aSb | ab\nT -> b", true),
+            ("S -> Sa | b\nT -> aTb", false),
+        ] {
+            let cfg = CFG::load_from_str(rules).unwrap();
+            assert_eq!(cfg.is_self_embedding(), expected, "{}", rules);
+        }
+    }
+
+    #[test]
+    fn is_right_recursive() {
+        let nt = |x: &str| Nonterminal::new(x.to_string(), 0);
+        let cfg = CFG::load_from_str("S -> LS | L\nL -> aL | a\nA -> AL | Lb | a").unwrap();
+        assert!(cfg.is_right_recursive(&nt("L")));
+        assert!(cfg.is_right_recursive(&nt("S")));
+        assert!(!cfg.is_right_recursive(&nt("A")));
+        assert!(!cfg.is_right_recursive(&nt("B")));
+        assert!(cfg.to_ebnf().contains("L = { \"a\" } , \"a\" ;"));
+    }
+
+    #[test]
+    fn remove_left_recursion() {
+        for test_rules in vec![
+            "A -> Aa | b",
+            "S -> Aa | b\nA -> Sc | d",
+            "S -> ASb | c\nA -> Sa | ",
+        ] {
+            let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+            assert!(cfg.is_left_recursive(), "{}", test_rules);
+            let new_cfg = cfg.remove_left_recursion();
+            assert!(!new_cfg.is_left_recursive(), "{}", new_cfg);
+            let expected: HashSet<Vec<Symbol>> =
+                Generator::new(cfg.simplify(), 0, 6, true).unwrap().collect();
+            let generated: HashSet<Vec<Symbol>> =
+                Generator::new(new_cfg.simplify(), 0, 6, true).unwrap().collect();
+            assert_eq!(generated, expected);
+        }
+
+        let test_rules = "
+            S -> aSb | ab
+        ";
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert!(!cfg.is_left_recursive());
+        assert_eq!(cfg.remove_left_recursion(), cfg);
+    }
+
+    #[test]
+    fn first_sets() {
+        let test_rules = "
+            S -> aB | b
+            B -> c
+        ";
+        let first = CFG::load_from_reader(Cursor::new(test_rules))
+            .unwrap()
+            .first_sets();
+        let terminals = |x: &str| -> HashSet<Lookahead> {
+            x.chars().map(|c| Lookahead::T(Terminal::new(c))).collect()
+        };
+        assert_eq!(first[&Nonterminal::new("S".to_string(), 0)], terminals("ab"));
+        assert_eq!(first[&Nonterminal::new("B".to_string(), 0)], terminals("c"));
+
+        let test_rules = "
+            S -> ABc | d
+            A -> a |
+            B -> b |
+        ";
+        let first = CFG::load_from_reader(Cursor::new(test_rules))
+            .unwrap()
+            .first_sets();
+        assert_eq!(first[&Nonterminal::new("S".to_string(), 0)], terminals("abcd"));
+        let mut expected = terminals("a");
+        expected.insert(Lookahead::Epsilon);
+        assert_eq!(first[&Nonterminal::new("A".to_string(), 0)], expected);
+    }
+
+    #[test]
+    fn follow_sets() {
+        let test_rules = "
+            E -> TX
+            X -> +TX |
+            T -> FY
+            Y -> *FY |
+            F -> (E) | a
+        ";
+        let follow = CFG::load_from_reader(Cursor::new(test_rules))
+            .unwrap()
+            .follow_sets();
+        let items = |x: &str| -> HashSet<Lookahead> {
+            let mut set: HashSet<Lookahead> =
+                x.chars().map(|c| Lookahead::T(Terminal::new(c))).collect();
+            set.insert(Lookahead::End);
+            set
+        };
+        let nonterm = |x: &str| Nonterminal::new(x.to_string(), 0);
+        assert_eq!(follow[&nonterm("E")], items(")"));
+        assert_eq!(follow[&nonterm("X")], items(")"));
+        assert_eq!(follow[&nonterm("T")], items("+)"));
+        assert_eq!(follow[&nonterm("Y")], items("+)"));
+        assert_eq!(follow[&nonterm("F")], items("*+)"));
+    }
+
+    #[test]
+    fn accepts_cyk() {
+        let cfg = CFG::load_from_reader(Cursor::new("S -> aSb | ab")).unwrap();
+        assert!(cfg.accepts_cyk("ab"));
+        assert!(cfg.accepts_cyk("aabb"));
+        assert!(!cfg.accepts_cyk("aab"));
+        assert!(!cfg.accepts_cyk(""));
+
+        let cfg = CFG::load_from_reader(Cursor::new("S -> aSb | ")).unwrap();
+        assert!(cfg.accepts_cyk(""));
+        assert!(cfg.accepts_cyk("aaabbb"));
+        assert!(!cfg.accepts_cyk("abab"));
+    }
+
+    #[test]
+    fn is_ambiguous() {
+        let cfg = CFG::load_from_reader(Cursor::new("E -> E+E | E*E | a")).unwrap();
+        let word = cfg.is_ambiguous(5).unwrap();
+        assert_eq!(word.len(), 5);
+        assert!(cfg.is_ambiguous(3).is_none());
+
+        let cfg = CFG::load_from_reader(Cursor::new("S -> aSb | ab")).unwrap();
+        assert!(cfg.is_ambiguous(10).is_none());
+
+        let cfg = CFG::load_from_reader(Cursor::new("S -> AS | a\nA -> b |")).unwrap();
+        assert_eq!(cfg.is_ambiguous(1), Some(vec![Symbol::T(Terminal::new('a'))]));
+
+        // the cycles that never reach a short enough string
+        let cfg = CFG::load_from_str("S -> S | aa").unwrap();
+        assert!(cfg.is_ambiguous(1).is_none());
+        assert_eq!(cfg.is_ambiguous(2), Some(CFG::parse_rhs("aa").unwrap()));
+        let cfg = CFG::load_from_str("S -> AS | aa\nA -> ε").unwrap();
+        assert!(cfg.is_ambiguous(1).is_none());
+        let cfg = CFG::load_from_str("S -> SA | a\nA -> ε").unwrap();
+        assert_eq!(cfg.is_ambiguous(1), Some(CFG::parse_rhs("a").unwrap()));
+    }
+
+    #[test]
+    fn num_derivations() {
+        let cfg = CFG::load_from_str("E -> E+E | a").unwrap();
+        assert_eq!(cfg.num_derivations("a"), 1);
+        assert_eq!(cfg.num_derivations("a+a"), 1);
+        assert_eq!(cfg.num_derivations("a+a+a"), 2);
+        assert_eq!(cfg.num_derivations("a+a+a+a"), 5);
+        assert_eq!(cfg.num_derivations("a+"), 0);
+
+        let cfg = CFG::load_from_str("S -> aSb | ε").unwrap();
+        assert_eq!(cfg.num_derivations(""), 1);
+        assert_eq!(cfg.num_derivations("aabb"), 1);
+        assert_eq!(cfg.num_derivations("aab"), 0);
+
+        // the cycles S -> A -> S are not repeated
+        let cfg = CFG::load_from_str("S -> A | a\nA -> S | a").unwrap();
+        assert_eq!(cfg.num_derivations("a"), 2);
+    }
+
+    #[test]
+    fn dependency_graph() {
+        let nt = |x: &str| Nonterminal::new(x.to_string(), 0);
+        let cfg = CFG::load_from_str("S -> aSA | BC | c\nA -> a\nB -> bD").unwrap();
+        let graph = cfg.dependency_graph();
+        let start: HashSet<Nonterminal> = vec![nt("S"), nt("A"), nt("B"), nt("C")]
+            .into_iter()
+            .collect();
+        assert_eq!(graph[&nt("S")], start);
+        assert!(graph[&nt("A")].is_empty());
+        assert_eq!(graph[&nt("B")], vec![nt("D")].into_iter().collect());
+        assert!(graph[&nt("C")].is_empty());
+        assert_eq!(graph.len(), 5);
+    }
+
+    #[test]
+    fn dependency_cycles() {
+        let nt = |x: &str| Nonterminal::new(x.to_string(), 0);
+        let cfg = CFG::load_from_str("S -> Az\nA -> Bx | a\nB -> Ay").unwrap();
+        assert_eq!(cfg.dependency_cycles(), vec![vec![nt("A"), nt("B")]]);
+
+        let cfg = CFG::load_from_str("S -> SA | C\nA -> a\nC -> DE\nD -> C | d\nE -> E").unwrap();
+        assert_eq!(
+            cfg.dependency_cycles(),
+            vec![vec![nt("C"), nt("D")], vec![nt("E")], vec![nt("S")]]
+        );
+
+        let cfg = CFG::load_from_str("S -> AB\nA -> a\nB -> Ab").unwrap();
+        assert!(cfg.dependency_cycles().is_empty());
+
+        // '<A0> -> a<A1>', ..., '<An> -> ε' is deeper than the call stack allows to recurse
+        let size = 20000;
+        let nt = |x: usize| Nonterminal::new("A".to_string(), x as u32);
+        let a = Symbol::T(Terminal::new('a'));
+        let mut productions: BTreeSet<Production> = (0..size)
+            .map(|x| Production::new(nt(x), vec![a.clone(), Symbol::N(nt(x + 1))]))
+            .collect();
+        productions.insert(Production::new(nt(size), vec![]));
+        let cfg = CFG::new(nt(0), productions.clone());
+        assert!(cfg.dependency_cycles().is_empty());
+        productions.insert(Production::new(nt(size), vec![Symbol::N(nt(0))]));
+        let cycles = CFG::new(nt(0), productions).dependency_cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].len(), size + 1);
+    }
+
+    #[test]
+    fn language_contains_epsilon() {
+        let cfg = CFG::load_from_str("S -> aS | ε").unwrap();
+        assert!(cfg.language_contains_epsilon());
+        let cfg = CFG::load_from_str("S -> aS | a").unwrap();
+        assert!(!cfg.language_contains_epsilon());
+        let cfg = CFG::load_from_str("S -> AB\nA -> a | ε\nB -> AA").unwrap();
+        assert!(cfg.language_contains_epsilon());
+    }
+
+    #[test]
+    fn is_empty_language() {
+        let cfg = CFG::load_from_reader(Cursor::new("S -> aS")).unwrap();
+        assert!(cfg.is_empty_language());
+        let cfg = CFG::load_from_reader(Cursor::new("S -> a")).unwrap();
+        assert!(!cfg.is_empty_language());
+        let cfg = CFG::load_from_reader(Cursor::new("S -> AS | \nA -> aA")).unwrap();
+        assert!(!cfg.is_empty_language());
+    }
+
+    #[test]
+    fn is_finite_language() {
+        let cfg = CFG::load_from_reader(Cursor::new("S -> aS | a")).unwrap();
+        assert!(!cfg.is_finite_language());
+        let cfg = CFG::load_from_reader(Cursor::new("S -> aA\nA -> b")).unwrap();
+        assert!(cfg.is_finite_language());
+        let cfg = CFG::load_from_reader(Cursor::new("S -> A | a\nA -> S | b")).unwrap();
+        assert!(cfg.is_finite_language());
+        let cfg = CFG::load_from_reader(Cursor::new("S -> AB\nA -> aB | a\nB -> Ab")).unwrap();
+        assert!(!cfg.is_finite_language());
+        let cfg = CFG::load_from_reader(Cursor::new("S -> a | B\nB -> bB")).unwrap();
+        assert!(cfg.is_finite_language());
+        let test_rules = "S -> CA\nA -> b\nC -> cD | c\nD -> C";
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert!(!cfg.is_finite_language());
+    }
+
+    #[test]
+    fn to_dot() {
+        let test_rules = "
+            S -> aSA | \"
+            A -> b
+        ";
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    r#"digraph CFG {"#,
+                    r#"    "A" [shape=circle];"#,
+                    r#"    "S" [shape=doublecircle];"#,
+                    r#"    "'\"'" [shape=box, label="\""];"#,
+                    r#"    "'a'" [shape=box, label="a"];"#,
+                    r#"    "'b'" [shape=box, label="b"];"#,
+                    r#"    "A" -> "'b'" [label="b"];"#,
+                    r#"    "S" -> "'\"'" [label="\""];"#,
+                    r#"    "S" -> "'a'" [label="aSA"];"#,
+                    r#"    "S" -> "S" [label="aSA"];"#,
+                    r#"    "S" -> "A" [label="aSA"];"#,
+                    r#"}"#,
+                ],
+                "\n"
+            )
+        );
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert_eq!(cfg.to_dot(true), expected);
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    r#"digraph CFG {"#,
+                    r#"    "A" [shape=circle];"#,
+                    r#"    "S" [shape=doublecircle];"#,
+                    r#"    "S" -> "S" [label="aSA"];"#,
+                    r#"    "S" -> "A" [label="aSA"];"#,
+                    r#"}"#,
+                ],
+                "\n"
+            )
+        );
+        assert_eq!(cfg.to_dot(false), expected);
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let test_rules = "
+            <S2> -> <S1><Some>a | Sa |
+            <S1> -> (<S1>) |
+            <Some> -> b
+        ";
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        let text = serde_yaml::to_string(&cfg).unwrap();
+        assert!(text.contains("<S1>"));
+        let loaded: CFG = serde_yaml::from_str(&text).unwrap();
+        assert_eq!(loaded, cfg);
+    }
+
+    #[test]
+    fn load_from_str() {
+        let test_rules = "
+            # comment
+            S -> aSb |
+
+            S -> ab
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        assert_eq!(cfg, CFG::load_from_reader(Cursor::new(test_rules)).unwrap());
+        assert_eq!(cfg.productions.len(), 3);
+    }
+
+    #[test]
+    fn long_nonterminal_names() {
+        let test_rules = "
+            <Stmt> -> if <Expr> then <Stmt> | <Выражение2>
+            <Expr> -> a | b
+            <Выражение2> -> c
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let stmt = Nonterminal::new("Stmt".to_string(), 0);
+        let expr = Nonterminal::new("Expr".to_string(), 0);
+        let rus = Nonterminal::new("Выражение".to_string(), 2);
+        assert_eq!(cfg.start, stmt);
+        let mut right = CFG::parse_rhs("if ").unwrap();
+        right.push(Symbol::N(expr.clone()));
+        right.extend(CFG::parse_rhs(" then ").unwrap());
+        right.push(Symbol::N(stmt.clone()));
+        assert!(cfg.productions.contains(&Production::new(stmt.clone(), right)));
+        assert!(cfg.productions.contains(&Production::new(stmt, vec![Symbol::N(rus.clone())])));
+        assert_eq!(format!("{}", rus), "<Выражение2>");
+        assert_eq!(cfg.nonterminals().len(), 3);
+    }
+
+    #[test]
+    fn explicit_epsilon() {
+        let cfg = CFG::load_from_str("A -> ε | a").unwrap();
+        let cfg_empty = CFG::load_from_str("A -> | a").unwrap();
+        assert_eq!(cfg, cfg_empty);
+        assert_eq!(format!("{}", cfg), "A ->  | a\n");
+        assert_eq!(format!("{:#}", cfg), "A -> a | ε\n");
+
+        let cfg = CFG::load_from_str("S -> ε").unwrap();
+        assert!(cfg.nullable_symbols().contains(&cfg.start));
+        let cfg = cfg.remove_epsilon_rules();
+        assert!(cfg.nullable_symbols().contains(&cfg.start));
+        assert_eq!(format!("{:#}", cfg), "S -> ε\n");
+    }
+
+    #[test]
+    fn error_line_number() {
+        let test_rules = "# grammar with a typo
+            S -> aSb | A
+
+            A -> c <B
+            B -> b
+        ";
+        let err = CFG::load_from_str(test_rules).unwrap_err();
+        assert_eq!(err.line(), Some(4));
+        assert_eq!(format!("{}", err), "Bad rule on line 4: A -> c <B");
+        let err = CFG::parse_production("A -> c <B", false).unwrap_err();
+        assert_eq!(err.line(), None);
+        assert_eq!(format!("{}", err), "Bad rule: A -> c <B");
+    }
+
+    #[test]
+    fn display_is_sorted() {
+        let cfg = CFG::load_from_str("S -> B | A\n<B2> -> b\nB -> <B2>\nA -> a").unwrap();
+        let same = CFG::load_from_str("S -> A | B\nA -> a\nB -> <B2>\n<B2> -> b").unwrap();
+        let expected = "S -> A | B\nA -> a\nB -> <B2>\n<B2> -> b\n";
+        assert_eq!(format!("{}", cfg), expected);
+        assert_eq!(format!("{}", same), expected);
+    }
+
+    #[test]
+    fn generating_symbols() {
+        let test_rules = "
+            S -> aA | B
+            A -> b | C
+            B -> Bb
+            C -> aC
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let expected: HashSet<Nonterminal> = vec!["S", "A"]
+            .into_iter()
+            .map(|x| Nonterminal::new(x.to_string(), 0))
+            .collect();
+        assert_eq!(cfg.generating_symbols(), expected);
+    }
+
+    #[test]
+    fn nullable_and_reachable_symbols() {
+        let to_set = |names: Vec<&str>| -> HashSet<Nonterminal> {
+            names
+                .into_iter()
+                .map(|x| Nonterminal::new(x.to_string(), 0))
+                .collect()
+        };
+        let cfg = CFG::load("sample/cfg/cfg.txt").unwrap();
+        assert_eq!(cfg.nullable_symbols(), to_set(vec!["G"]));
+        assert_eq!(
+            cfg.reachable_symbols(),
+            to_set(vec!["S", "A", "B", "C", "D", "E", "F", "G"])
         );
-        let cfg = CFG::load_from_reader(Cursor::new(test_rules))
+        let cfg = CFG::load("sample/cfg/break-reverse-order-simplification.txt").unwrap();
+        assert_eq!(cfg.nullable_symbols(), to_set(vec!["A", "B"]));
+        assert_eq!(cfg.reachable_symbols(), to_set(vec!["A", "B"]));
+        let cfg = CFG::load("sample/cfg/simple-arithmetic.txt").unwrap();
+        assert_eq!(cfg.nullable_symbols(), to_set(vec![]));
+        assert_eq!(cfg.reachable_symbols(), to_set(vec!["E", "T", "F", "I"]));
+
+        let cfg = CFG::load_from_str("S -> aA |\nA -> b\nC -> A | c").unwrap();
+        assert_eq!(cfg.nullable_symbols(), to_set(vec!["S"]));
+        assert_eq!(cfg.reachable_symbols(), to_set(vec!["S", "A"]));
+    }
+
+    // cargo test --release fixpoint_timing -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn fixpoint_timing() {
+        use std::time::Instant;
+        // the fixpoint over the names, as it was before InternedRules
+        let by_names = |cfg: &CFG, nullable: bool| -> HashSet<Nonterminal> {
+            let mut marked: HashSet<Nonterminal> = HashSet::new();
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for rule in &cfg.productions {
+                    let ready = rule.right.iter().all(|x| match x {
+                        &Symbol::N(ref n) => marked.contains(n),
+                        &Symbol::T(_) => !nullable,
+                    });
+                    if ready && marked.insert(rule.left.clone()) {
+                        changed = true;
+                    }
+                }
+            }
+            marked
+        };
+        // '<A0> -> <A1><A1> | <A1>a', ..., '<An> -> ε': the rules are visited in the name order,
+        // so every pass over them marks one more Nonterminal
+        let size = 3000;
+        let nt = |x: usize| Nonterminal::new("A".to_string(), x as u32);
+        let mut productions = BTreeSet::new();
+        for idx in 0..size {
+            let next = Symbol::N(nt(idx + 1));
+            let term = Symbol::T(Terminal::new('a'));
+            productions.insert(Production::new(nt(idx), vec![next.clone(), next.clone()]));
+            productions.insert(Production::new(nt(idx), vec![next, term]));
+        }
+        productions.insert(Production::new(nt(size), vec![]));
+        let cfg = CFG::new(nt(0), productions);
+
+        let now = Instant::now();
+        let expected = (by_names(&cfg, true), by_names(&cfg, false));
+        let names_time = now.elapsed();
+        let now = Instant::now();
+        let interned = (cfg.nullable_symbols(), cfg.generating_symbols());
+        let interned_time = now.elapsed();
+        assert_eq!(interned, expected);
+        assert_eq!(interned.0.len(), size + 1);
+        println!("names: {:?}, interned ids: {:?}", names_time, interned_time);
+    }
+
+    #[test]
+    fn undefined_nonterminals() {
+        let cfg = CFG::load_from_str("S -> aB | A\nA -> a").unwrap();
+        let expected: HashSet<Nonterminal> =
+            vec![Nonterminal::new("B".to_string(), 0)].into_iter().collect();
+        assert_eq!(cfg.undefined_nonterminals(), expected);
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        assert!(cfg.undefined_nonterminals().is_empty());
+    }
+
+    #[test]
+    fn stats() {
+        let cfg = CFG::load("sample/cfg/brackets.txt").unwrap();
+        let expected = GrammarStats {
+            productions: 2,
+            nonterminals: 1,
+            terminals: 2,
+            max_rhs_len: 5,
+            epsilon_productions: 1,
+            unit_productions: 0,
+        };
+        assert_eq!(cfg.stats(), expected);
+
+        let cfg = CFG::load("sample/cfg/simple-arithmetic.txt").unwrap();
+        let expected = GrammarStats {
+            productions: 12,
+            nonterminals: 4,
+            terminals: 8,
+            max_rhs_len: 3,
+            epsilon_productions: 0,
+            unit_productions: 3,
+        };
+        assert_eq!(cfg.stats(), expected);
+
+        let cfg = CFG::new(Nonterminal::new("S".to_string(), 0), BTreeSet::new());
+        assert_eq!(cfg.stats().max_rhs_len, 0);
+        assert_eq!(cfg.stats().nonterminals, 0);
+    }
+
+    #[test]
+    fn validate() {
+        let test_rules = "
+            S -> aB | A
+            A -> aA
+            C -> c
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let diagnostics: Vec<String> = cfg.validate().iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "warning: Nonterminal A does not derive any terminal string",
+                "error: undefined Nonterminal B in the rule 'S -> aB', \
+                 use a SymbolClassifier to read it as a terminal",
+                "warning: unreachable Nonterminal C",
+                "warning: Nonterminal S does not derive any terminal string",
+            ]
+        );
+
+        let cfg = CFG::new(Nonterminal::new("S".to_string(), 0), BTreeSet::new());
+        assert_eq!(
+            cfg.validate(),
+            vec![Diagnostic::new(Severity::Error, "start symbol S has no rules".to_string())]
+        );
+        assert!(CFG::load_from_str("S -> aSb | ab").unwrap().validate().is_empty());
+
+        let cfg = CFG::load_from_str("S -> X<Expr> | a\n<Expr> -> <Term>").unwrap();
+        let diagnostics: Vec<String> = cfg.validate().iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "warning: Nonterminal <Expr> does not derive any terminal string",
+                "error: undefined Nonterminal <Term> in the rule '<Expr> -> <Term>'",
+                "error: undefined Nonterminal X in the rule 'S -> X<Expr>', \
+                 use a SymbolClassifier to read it as a terminal",
+            ]
+        );
+        let classifier = |ch: char| ch != 'S';
+        let input = Cursor::new("S -> XS | Y");
+        let cfg = CFG::load_cfg_from_reader_with(input, false, &classifier).unwrap();
+        assert!(cfg.validate().is_empty());
+    }
+
+    #[test]
+    fn cfg_builder() {
+        let cfg = CfgBuilder::new()
+            .rule("S", "aSb")
+            .rule("S", "")
+            .rule("<Expr1>", "a | ε")
+            .build()
+            .unwrap();
+        let expected = CFG::load_from_str("S -> aSb |\n<Expr1> -> a |").unwrap();
+        assert_eq!(cfg, expected);
+
+        let cfg = CfgBuilder::new().start("A").rule("S", "A").rule("A", "a").build();
+        assert_eq!(cfg.unwrap().start, Nonterminal::new("A".to_string(), 0));
+
+        match CfgBuilder::new().rule("S", "a").rule("S", "<a").build() {
+            Err(CfgError::BadRule { line: 2, .. }) => {}
+            x => panic!("Unexpected result: {:?}", x),
+        }
+        assert!(CfgBuilder::new().start("a").rule("S", "a").build().is_err());
+        assert!(CfgBuilder::new().build().is_err());
+    }
+
+    #[test]
+    fn add_remove_production() {
+        let mut cfg = CFG::load_from_str("S -> a").unwrap();
+        let start = cfg.start.clone();
+        let a = Nonterminal::new("A".to_string(), 0);
+        let to_a = Production::new(start.clone(), vec![Symbol::N(a.clone())]);
+        let a_rule = Production::new(a.clone(), CFG::parse_rhs("b").unwrap());
+        assert!(cfg.add_production(to_a.clone()));
+        assert!(!cfg.add_production(to_a.clone()));
+        assert!(cfg.add_production(a_rule.clone()));
+        assert!(cfg.nonterminals().contains(&a));
+        assert_eq!(format!("{}", cfg), "S -> A | a\nA -> b\n");
+
+        assert!(cfg.remove_production(&to_a));
+        assert!(!cfg.remove_production(&to_a));
+        assert!(cfg.remove_production(&a_rule));
+        assert!(!cfg.nonterminals().contains(&a));
+        assert!(cfg.remove_production(&Production::new(start, CFG::parse_rhs("a").unwrap())));
+        assert!(cfg.productions.is_empty());
+        assert!(cfg.is_empty_language());
+        assert_eq!(format!("{}", cfg), "S -> \n");
+    }
+
+    #[test]
+    fn union() {
+        let left = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let right = CFG::load_from_str("S -> cS | A\nA -> c |").unwrap();
+        let union = left.union(&right);
+        let mut vars = left.nonterminals();
+        vars.extend(right.nonterminals());
+        assert_eq!(union.nonterminals().len(), vars.len() + 2);
+        assert!(!vars.contains(&union.start));
+
+        let mut expected: HashSet<Vec<Symbol>> = Generator::new(left, 0, 6, true)
             .unwrap()
-            .remove_useless_rules();
-        assert_eq!(format!("{}", cfg.remove_unreachable_rules()), expected);
+            .collect();
+        expected.extend(Generator::new(right, 0, 6, true).unwrap());
+        let generated: HashSet<Vec<Symbol>> = Generator::new(union, 0, 6, true).unwrap().collect();
+        assert!(generated.contains(&CFG::parse_rhs("aabb").unwrap()));
+        assert!(generated.contains(&CFG::parse_rhs("ccc").unwrap()));
+        assert_eq!(generated, expected);
     }
 
     #[test]
-    fn simplify() {
+    fn concat() {
+        let left = CFG::load_from_str("S -> aSb |").unwrap();
+        let right = CFG::load_from_str("S -> cS | A\nA -> b").unwrap();
+        let concat = left.concat(&right);
+
+        let left: Vec<Vec<Symbol>> = Generator::new(left, 0, 6, true).unwrap().collect();
+        let right: Vec<Vec<Symbol>> = Generator::new(right, 0, 6, true).unwrap().collect();
+        let mut expected = HashSet::new();
+        for x in &left {
+            for y in &right {
+                if x.len() + y.len() <= 6 {
+                    let mut item = x.clone();
+                    item.extend(y.iter().cloned());
+                    expected.insert(item);
+                }
+            }
+        }
+        let generated: HashSet<Vec<Symbol>> = Generator::new(concat, 0, 6, true).unwrap().collect();
+        assert!(generated.contains(&CFG::parse_rhs("b").unwrap()));
+        assert!(generated.contains(&CFG::parse_rhs("abccb").unwrap()));
+        assert_eq!(generated, expected);
+    }
+
+    #[test]
+    fn star() {
+        let cfg = CFG::load_from_str("S -> aSb | c").unwrap();
+        let star = cfg.star();
+        assert!(star.nullable_symbols().contains(&star.start));
+        // the Generator drops the sentential forms longer than the limit,
+        // so with 'S0 -> S0S | ε' it misses long words until the epsilon rules are removed
+        let with_epsilon: HashSet<Vec<Symbol>> = Generator::new(star, 0, 6, true)
+            .unwrap()
+            .collect();
+        let star = cfg.star().remove_epsilon_rules();
+        let generated: HashSet<Vec<Symbol>> = Generator::new(star, 0, 6, true).unwrap().collect();
+        assert!(with_epsilon.is_subset(&generated));
+        assert!(generated.contains(&vec![]));
+        assert!(generated.contains(&CFG::parse_rhs("cacb").unwrap()));
+        assert!(generated.contains(&CFG::parse_rhs("cccccc").unwrap()));
+        for x in &generated {
+            for y in generated.iter().filter(|y| x.len() + y.len() <= 6) {
+                let mut item = x.clone();
+                item.extend(y.iter().cloned());
+                assert!(generated.contains(&item), "{:?}", item);
+            }
+        }
+    }
+
+    #[test]
+    fn rename_nonterminal() {
+        let mut cfg = CFG::load_from_str("S -> aA | AB\nA -> aA | b\nB -> A").unwrap();
+        let a = Nonterminal::new("A".to_string(), 0);
+        let z = Nonterminal::new("Z".to_string(), 0);
+        cfg.rename_nonterminal(&a, z.clone()).unwrap();
+        assert!(!cfg.nonterminals().contains(&a));
+        assert_eq!(format!("{}", cfg), "S -> ZB | aZ\nB -> Z\nZ -> aZ | b\n");
+
+        let start = cfg.start.clone();
+        cfg.rename_nonterminal(&start, Nonterminal::new("S".to_string(), 1)).unwrap();
+        assert_eq!(format!("{}", cfg.start), "<S1>");
+
+        let b = Nonterminal::new("B".to_string(), 0);
+        match cfg.rename_nonterminal(&b, z.clone()) {
+            Err(CfgError::NonterminalExists(ref n)) if n == &z => {}
+            x => panic!("Unexpected result: {:?}", x),
+        }
+        assert!(cfg.nonterminals().contains(&b));
+    }
+
+    #[test]
+    fn structural_equality() {
+        let cfg = CFG::load_from_str("S -> aSb | ab\nA -> a").unwrap();
+        assert_eq!(cfg, CFG::load_from_str("S -> ab\nA -> a\nS -> aSb").unwrap());
+        assert_ne!(cfg, CFG::load_from_str("S -> aSb | ab").unwrap());
+        // the same rules, but the start symbol differs
+        assert_ne!(cfg, CFG::load_from_str("A -> a\nS -> ab | aSb\n").unwrap());
+        let mut renamed = CFG::load_from_str("S -> aSb | ab\nA -> a").unwrap();
+        let a = Nonterminal::new("A".to_string(), 0);
+        renamed.rename_nonterminal(&a, Nonterminal::new("B".to_string(), 0)).unwrap();
+        assert_ne!(cfg, renamed);
+    }
+
+    #[test]
+    fn is_regular() {
+        for &(test_rules, expected) in &[
+            ("S -> aS | b", Some(Linearity::Right)),
+            ("S -> abA | \nA -> cS | c", Some(Linearity::Right)),
+            ("S -> Sa | b", Some(Linearity::Left)),
+            ("S -> Aab | \nA -> Sc | c", Some(Linearity::Left)),
+            ("S -> a | bc", Some(Linearity::Right)),
+            ("S -> aSb", None),
+            ("S -> aA | Ab\nA -> a", None),
+            ("S -> AA\nA -> a", None),
+        ] {
+            let cfg = CFG::load_from_str(test_rules).unwrap();
+            assert_eq!(cfg.is_regular(), expected, "{}", test_rules);
+        }
+    }
+
+    #[test]
+    fn reverse() {
+        let cfg = CFG::load_from_str("S -> aSbc | A\nA -> dA | e").unwrap();
+        let reversed = cfg.reverse();
+        assert_eq!(format!("{}", reversed), "S -> A | cbSa\nA -> Ad | e\n");
+        let expected: HashSet<Vec<Symbol>> = Generator::new(cfg, 0, 8, true).unwrap()
+            .map(|x| x.into_iter().rev().collect())
+            .collect();
+        let generated: HashSet<Vec<Symbol>> = Generator::new(reversed, 0, 8, true)
+            .unwrap()
+            .collect();
+        assert!(generated.contains(&CFG::parse_rhs("cbedda").unwrap()));
+        assert_eq!(generated, expected);
+    }
+
+    #[test]
+    fn map_terminals() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let mapped = cfg.map_terminals(|x| if x == 'a' { vec!['x', 'x'] } else { vec![x] });
+        assert_eq!(format!("{}", mapped), "S -> xxSb | xxb\n");
+        let generated: Vec<String> = Generator::new(mapped, 0, 9, true)
+            .unwrap()
+            .strings()
+            .collect();
+        assert_eq!(generated, vec!["xxb", "xxxxbb", "xxxxxxbbb"]);
+
+        let erased = cfg.map_terminals(|x| if x == 'a' { vec![] } else { vec![x] });
+        assert_eq!(format!("{}", erased), "S -> Sb | b\n");
+    }
+
+    #[test]
+    fn count_strings_of_length() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let counts: Vec<u64> = (0..7).map(|n| cfg.count_strings_of_length(n)).collect();
+        assert_eq!(counts, vec![0, 0, 1, 0, 1, 0, 1]);
+        // ambiguous grammar, every string is counted once
+        let cfg = CFG::load_from_str("S -> SS | a |").unwrap();
+        let counts: Vec<u64> = (0..5).map(|n| cfg.count_strings_of_length(n)).collect();
+        assert_eq!(counts, vec![1, 1, 1, 1, 1]);
+        let cfg = CFG::load_from_str("S -> aS | bS | a | b").unwrap();
+        assert_eq!(cfg.count_strings_of_length(5), 32);
+    }
+
+    #[test]
+    fn shortest_string() {
+        let cfg = CFG::load_from_str("S -> aaaa | bb").unwrap();
+        assert_eq!(cfg.shortest_string(), Some(CFG::parse_rhs("bb").unwrap()));
+        let cfg = CFG::load_from_str("S -> AB | aaaa\nA -> aA | a\nB -> BB | b").unwrap();
+        assert_eq!(cfg.shortest_string(), Some(CFG::parse_rhs("ab").unwrap()));
+        let cfg = CFG::load_from_str("S -> aSb | A\nA -> cA |").unwrap();
+        assert_eq!(cfg.shortest_string(), Some(vec![]));
+        let cfg = CFG::load_from_str("S -> aS | A\nA -> Ab").unwrap();
+        assert_eq!(cfg.shortest_string(), None);
+    }
+
+    #[test]
+    fn symbol_classifier() {
         let test_rules = "
-            S ->  | S(S)S
+            E -> E+T | T
+            T -> T*F | F
+            F -> (E) | X | Y
         ";
-        let expected = format!(
-            "{}\n",
-            join(
-                vec![
-                    "<S1> ->  | () | ()S | (S) | (S)S | S() | S()S | S(S) | S(S)S",
-                    "S -> () | ()S | (S) | (S)S | S() | S()S | S(S) | S(S)S",
-                ],
-                "\n"
-            )
+        let classifier = |ch: char| ch != 'E' && ch != 'T' && ch != 'F';
+        let input = Cursor::new(test_rules);
+        let cfg = CFG::load_cfg_from_reader_with(input, false, &classifier).unwrap();
+        let terminals: String = cfg.terminals().iter().map(|x| x.symbol).collect();
+        assert_eq!(terminals, "()*+XY");
+        assert_eq!(cfg.nonterminals().len(), 3);
+        assert!(cfg.accepts_cyk("X*(Y+X)"));
+
+        let err = CFG::parse_production_with("X -> a", false, &classifier).unwrap_err();
+        assert_eq!(format!("{}", err), "Terminal symbol at LHS: X -> a");
+
+        let lowercase = |ch: char| !ch.is_lowercase();
+        let input = Cursor::new("s -> AsB |");
+        let cfg = CFG::load_cfg_from_reader_with(input, false, &lowercase).unwrap();
+        assert_eq!(cfg.start, Nonterminal::new("s".to_string(), 0));
+        assert!(cfg.accepts_cyk("AABB"));
+        assert_eq!(CFG::parse_rhs_with("<S>a", &lowercase).unwrap().len(), 2);
+        assert!(CFG::parse_rhs_with("<S>a", &lowercase).unwrap()[1].is_nonterminal());
+    }
+
+    #[test]
+    fn escaped_metacharacters() {
+        let cfg = CFG::load_from_str(r"A -> \| | a").unwrap();
+        let right: Vec<Vec<Symbol>> = cfg.productions.iter().map(|x| x.right.clone()).collect();
+        let (a, pipe) = (Symbol::T(Terminal::new('a')), Symbol::T(Terminal::new('|')));
+        assert_eq!(right, vec![vec![a], vec![pipe]]);
+
+        let test_rules = r"
+            S -> S\>S | E | \-\> | \\
+            E -> a\|b|\ 
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let terminals: String = cfg.terminals().iter().map(|x| x.symbol).collect();
+        assert_eq!(terminals, " ->\\ab|");
+        assert_eq!(cfg.productions.len(), 6);
+        assert!(cfg.accepts_cyk("->>\\"));
+        assert!(cfg.accepts_cyk("a|b> "));
+        assert!(!cfg.accepts_cyk("a|b>"));
+        assert_eq!(CFG::load_from_str(&cfg.to_string()).unwrap(), cfg);
+
+        assert!(CFG::load_from_str(r"S -> a\").is_err());
+        assert!(CFG::load_from_str(r"S -> a -> b").is_err());
+        assert!(CFG::load_from_str(r"S -> a \-\> b").is_ok());
+    }
+
+    #[test]
+    fn inline_comments() {
+        let test_rules = r"
+            S -> aSb | ab  # matched pairs
+            S -> A#B
+            A -> \# | a\ # the escaped space is kept
+            # the whole line comment
+            B -> \\#b
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        assert_eq!(
+            cfg,
+            CFG::load_from_str("S -> aSb | ab | A\nA -> \\# | a\\ \nB -> \\\\").unwrap()
         );
-        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
-        assert_eq!(format!("{}", cfg.simplify()), expected);
+        assert!(cfg.accepts_cyk("aabb"));
+        assert!(cfg.accepts_cyk("#"));
+        assert!(cfg.accepts_cyk("a "));
+        assert!(!cfg.accepts_cyk("a"));
+
+        // '#' is written escaped and read back as the terminal
+        let cfg = CFG::load_from_str(r"S -> \#S\# | x").unwrap();
+        assert_eq!(cfg.to_string(), "S -> \\#S\\# | x\n");
+        assert_eq!(CFG::load_from_str(&cfg.to_string()).unwrap(), cfg);
+        assert!(cfg.accepts_cyk("##x##"));
     }
 
     #[test]
-    fn chomsky() {
+    fn from_productions() {
+        let start = Nonterminal::new("S".to_string(), 0);
+        let rules = vec![
+            Production::new(start.clone(), CFG::parse_rhs("aSb").unwrap()),
+            Production::new(start.clone(), CFG::parse_rhs("ab").unwrap()),
+            Production::new(start.clone(), CFG::parse_rhs("ab").unwrap()),
+        ];
+        let cfg = CFG::from_productions(start, rules);
+        assert_eq!(cfg, CFG::load_from_str("S -> aSb | ab").unwrap());
+        let generated: Vec<String> = Generator::new(cfg, 0, 4, true).unwrap().strings().collect();
+        assert_eq!(generated, vec!["ab", "aabb"]);
+    }
+
+    #[test]
+    fn alternatives() {
+        let cfg = CFG::load("sample/cfg/simple-arithmetic.txt").unwrap();
+        let start = cfg.start.clone();
+        let alternatives: Vec<String> = cfg.alternatives(&start).map(|x| join(x, "")).collect();
+        assert_eq!(alternatives, vec!["E+T", "T"]);
+        let undefined = Nonterminal::new("X".to_string(), 0);
+        assert_eq!(cfg.alternatives(&undefined).count(), 0);
+
+        let rules = cfg.rules_by_lhs();
+        let names: Vec<String> = rules.keys().map(|x| x.to_string()).collect();
+        assert_eq!(names, vec!["E", "F", "I", "T"]);
+        assert_eq!(rules[&start], cfg.alternatives(&start).collect::<Vec<_>>());
+        assert_eq!(rules.values().map(|x| x.len()).sum::<usize>(), cfg.productions.len());
+    }
+
+    #[test]
+    fn remove_self_loops() {
+        let cfg = CFG::load_from_str("A -> A | a").unwrap();
+        assert_eq!(format!("{}", cfg.remove_self_loops()), "A -> a\n");
+        let cfg = CFG::load_from_str("S -> S | SS | B\nB -> B | S | b").unwrap();
+        assert_eq!(format!("{}", cfg.remove_self_loops()), "S -> B | SS\nB -> S | b\n");
+    }
+
+    #[test]
+    fn complement() {
+        let alphabet: HashSet<char> = "ab".chars().collect();
+        let cfg = CFG::load_from_str("S -> aS | b").unwrap();
+        let complement = cfg.complement(&alphabet).unwrap();
+        for input in vec!["", "a", "ba", "abb", "bb", "aaba"] {
+            assert!(complement.accepts_cyk(input), "Reject {}", input);
+        }
+        for input in vec!["b", "ab", "aaab", "c"] {
+            assert!(!complement.accepts_cyk(input), "Accept {}", input);
+        }
+        // the complement of Σ* is empty
+        let cfg = CFG::load_from_str("S -> aS | bS |").unwrap();
+        assert!(cfg.complement(&alphabet).unwrap().is_empty_language());
+        let cfg = CFG::load_from_str("S -> aSb |").unwrap();
+        assert_eq!(cfg.complement(&alphabet).unwrap_err(), NotRegular);
+    }
+
+    #[test]
+    fn language_equals_regular() {
+        let cfg = CFG::load_from_str("S -> aS | b").unwrap();
+        let left = CFG::load_from_str("S -> Ab | b\nA -> Aa | a").unwrap();
+        assert_eq!(left.is_regular(), Some(Linearity::Left));
+        assert_eq!(cfg.language_equals_regular(&left), Ok(true));
+        assert_eq!(left.language_equals_regular(&cfg), Ok(true));
+        assert_eq!(cfg.language_equals_regular(&cfg.simplify()), Ok(true));
+
+        let other = CFG::load_from_str("S -> Ab | b\nA -> Ab | a").unwrap();
+        assert_eq!(cfg.language_equals_regular(&other), Ok(false));
+        let other = CFG::load_from_str("S -> aS | b | c").unwrap();
+        assert_eq!(cfg.language_equals_regular(&other), Ok(false));
+        let other = CFG::load_from_str("S -> aSb | b").unwrap();
+        assert_eq!(cfg.language_equals_regular(&other), Err(NotRegular));
+    }
+
+    #[test]
+    fn witnesses() {
         let test_rules = "
-            A ->  BAB | B |
-            B -> 00 |
+            S -> AB | aC
+            A -> aA | ab | C
+            B -> BB
+            C -> c |
+            D -> Cd
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let witnesses: BTreeMap<String, Option<String>> = cfg
+            .witnesses()
+            .into_iter()
+            .map(|(n, w)| (n.to_string(), w.map(|x| join(x, ""))))
+            .collect();
+        let expected: BTreeMap<String, Option<String>> = vec![
+            ("S", Some("a")),
+            ("A", Some("")),
+            ("B", None),
+            ("C", Some("")),
+            ("D", Some("d")),
+        ].into_iter()
+        .map(|(n, w)| (n.to_string(), w.map(|x| x.to_string())))
+        .collect();
+        assert_eq!(witnesses, expected);
+    }
+
+    #[test]
+    fn start_directive() {
+        let test_rules = "
+            A -> aA | a
+            B -> b
+            # start: S
+            S -> AB
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        assert_eq!(cfg.start, Nonterminal::new("S".to_string(), 0));
+        assert_eq!(format!("{}", cfg), "S -> AB\nA -> a | aA\nB -> b\n");
+        let cfg = CFG::load_from_str("#start:<E1>\n<T> -> a\n<E1> -> <T>").unwrap();
+        assert_eq!(cfg.start, Nonterminal::new("E".to_string(), 1));
+        // the other comments are skipped as before
+        let cfg = CFG::load_from_str("# starting rule\nA -> a\nS -> A").unwrap();
+        assert_eq!(cfg.start, Nonterminal::new("A".to_string(), 0));
+
+        for input in vec!["# start: a\nS -> a", "# start:\nS -> a"] {
+            match CFG::load_from_str(input) {
+                Err(CfgError::BadRule { line: 1, .. }) => {}
+                x => panic!("Unexpected result: {:?}", x),
+            }
+        }
+        match CFG::load_from_str("# start: S") {
+            Err(CfgError::NoRules) => {}
+            x => panic!("Unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn left_factor() {
+        let cfg = CFG::load_from_str("S -> abc | abd | e").unwrap();
+        let factored = cfg.left_factor();
+        assert_eq!(format!("{}", factored), "S -> ab<S1> | e\n<S1> -> c | d\n");
+
+        let test_rules = "
+            S -> aA | aB | b
+            A -> ab | abc | a | ε
+            B -> Sc | Sd
         ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let factored = cfg.left_factor();
         let expected = format!(
             "{}\n",
             join(
                 vec![
-                    "<A1> ->  | <0><0> | AB | B<AB> | BA | BB",
-                    "<0> -> 0",
-                    "A -> <0><0> | AB | B<AB> | BA | BB",
-                    "<AB> -> AB",
-                    "B -> <0><0>",
+                    "S -> a<S1> | b",
+                    "A -> a<A1> | ε",
+                    "<A1> -> b<A2> | ε",
+                    "<A2> -> c | ε",
+                    "B -> S<B1>",
+                    "<B1> -> c | d",
+                    "<S1> -> A | B",
                 ],
                 "\n"
             )
         );
-        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
-        assert_eq!(format!("{}", cfg.chomsky()), expected);
+        assert_eq!(format!("{:#}", factored), expected);
+        assert_eq!(factored.left_factor(), factored);
+        // the sentential forms of the factored grammar are longer, so the generated sets
+        // differ by the bounds and the strings are checked with the other grammar
+        for (from, to) in vec![(&cfg, &factored), (&factored, &cfg)] {
+            let copy = CFG::new(from.start.clone(), from.productions.clone());
+            for seq in Generator::new(copy, 0, 6, true).unwrap().strings() {
+                assert!(to.accepts_cyk(&seq), "{}", seq);
+            }
+        }
+    }
+
+    #[test]
+    fn pretty_string() {
+        let cfg = CFG::load_from_str(
+            "# start: S\n\
+             <Expr> -> <Expr>+<Term> | <Term>\n\
+             S -> <Expr> | ε\n\
+             <Term> -> (<Expr>) | <Digit>\n\
+             <Digit> -> 0 | 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8 | 9 | a | b | c | d | e | f | g | h | i",
+        ).unwrap();
+        let expected = [
+            "S       -> <Expr> | ε",
+            "<Digit> -> 0 | 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8 | 9 | a | b | c | d | e | f | g | h",
+            "         | i",
+            "<Expr>  -> <Expr>+<Term> | <Term>",
+            "<Term>  -> (<Expr>) | <Digit>",
+            "",
+        ];
+        assert_eq!(cfg.to_pretty_string(), expected.join("\n"));
+    }
+
+    #[test]
+    fn symbol_accessors() {
+        let symbols = CFG::parse_rhs("aS<Expr><S1>").unwrap();
+        assert_eq!(symbols[0].as_terminal(), Some(&Terminal::new('a')));
+        assert_eq!(symbols[0].terminal_char(), Some('a'));
+        assert_eq!(symbols[0].nonterminal_char(), None);
+        assert_eq!(symbols[1].as_terminal(), None);
+        assert_eq!(symbols[1].terminal_char(), None);
+        assert_eq!(symbols[1].nonterminal_char(), Some('S'));
+        assert_eq!(symbols[2].nonterminal_char(), None);
+        assert_eq!(symbols[3].nonterminal_char(), None);
+    }
+
+    #[test]
+    fn to_source() {
+        let source = "\
+            # expressions\n\
+            E -> E+T | T\n\
+            T -> T*F\n\
+            F -> (E) | a  # the operand\n\
+            T -> F\n\
+            A -> a | ε\n";
+        let cfg = CFG::load_from_str(source).unwrap();
+        let expected = "E -> E+T | T\nT -> T*F | F\nF -> (E) | a\nA -> a | ε\n";
+        assert_eq!(cfg.to_source(), expected);
+        assert_eq!(CFG::load_from_str(expected).unwrap().to_source(), expected);
+
+        // the rules unknown to the file follow the read ones, the removed ones are skipped
+        let mut cfg = cfg;
+        let rule = |x: &str| CFG::parse_production(x, false).unwrap().remove(0);
+        cfg.productions.remove(&rule("A -> a"));
+        cfg.productions.insert(rule("B -> b"));
+        cfg.productions.insert(rule("E -> b"));
+        assert_eq!(
+            cfg.to_source(),
+            "E -> E+T | T | b\nT -> T*F | F\nF -> (E) | a\nA -> ε\nB -> b\n"
+        );
+
+        // a transformed grammar has no source order
+        let cfg = CFG::load_from_str("S -> b | a\nA -> x").unwrap().remove_unreachable_rules();
+        assert_eq!(cfg.to_source(), "S -> a | b\n");
+
+        // the builder and from_productions keep the order of the rules too
+        let cfg = CfgBuilder::new()
+            .rule("S", "bA | a")
+            .rule("A", "x")
+            .rule("S", "c")
+            .build()
+            .unwrap();
+        assert_eq!(cfg.to_source(), "S -> bA | a | c\nA -> x\n");
+        let cfg = CFG::from_productions(
+            Nonterminal::new("S".to_string(), 0),
+            vec![rule("B -> b"), rule("S -> B"), rule("B -> a")],
+        );
+        assert_eq!(cfg.to_source(), "B -> b | a\nS -> B\n");
+    }
+
+    #[test]
+    fn merge() {
+        let mut cfg = CFG::load_from_str("S -> AB | A\nA -> aA | a").unwrap();
+        let other = CFG::load_from_str("B -> bB | b\nA -> a").unwrap();
+        cfg.merge(other);
+        assert_eq!(cfg.start, Nonterminal::new("S".to_string(), 0));
+        assert_eq!(cfg.productions.len(), 6);
+        assert_eq!(cfg.to_source(), "S -> AB | A\nA -> aA | a\nB -> bB | b\n");
+        let generated: Vec<String> = Generator::new(cfg, 0, 2, true).unwrap().strings().collect();
+        assert_eq!(generated, vec!["a", "aa", "ab"]);
+    }
+
+    #[test]
+    fn binarize() {
+        let cfg = CFG::load_from_str("S -> aBcD | ab | \nB -> b\nD -> BcD | d").unwrap();
+        let binary = cfg.binarize();
+        let expected = [
+            "S -> a<S1> | ab | ε",
+            "B -> b",
+            "D -> B<D1> | d",
+            "<D1> -> cD",
+            "<S1> -> B<D1>",
+            "",
+        ];
+        assert_eq!(format!("{:#}", binary), expected.join("\n"));
+        assert!(binary.productions.iter().all(|x| x.right.len() <= 2));
+        // the chain reproduces the original right side
+        let path = binary.derive("abcd").unwrap();
+        assert_eq!(path.len(), 5);
+        assert_eq!(binary.binarize(), binary);
+    }
+
+    #[test]
+    fn symbol_order() {
+        let mut symbols = CFG::parse_rhs("b<A2>aB<A1>A").unwrap();
+        symbols.sort();
+        assert_eq!(join(&symbols, ""), "A<A1><A2>Bab");
+
+        let rule = |x: &str| CFG::parse_production(x, false).unwrap().remove(0);
+        let mut rules = vec![rule("B -> a"), rule("A -> ab"), rule("A -> a"), rule("A -> Ba")];
+        rules.sort();
+        let rules: Vec<String> = rules
+            .iter()
+            .map(|x| format!("{} -> {}", x.left, join(&x.right, "")))
+            .collect();
+        assert_eq!(rules, vec!["A -> Ba", "A -> a", "A -> ab", "B -> a"]);
+    }
+
+    #[test]
+    fn terminals_and_nonterminals() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let terminals: BTreeSet<Terminal> = "ab".chars().map(Terminal::new).collect();
+        assert_eq!(cfg.terminals(), terminals);
+        let cfg = CFG::load_from_str("S -> A<B1> | c\nA -> ε").unwrap();
+        let nonterminals: Vec<String> = cfg.nonterminals().iter().map(|x| x.to_string()).collect();
+        assert_eq!(nonterminals, vec!["A", "<B1>", "S"]);
+        assert_eq!(cfg.terminals().len(), 1);
+    }
+
+    #[test]
+    fn normalize() {
+        let cfg =
+            CFG::load_from_str("S -> aA | aBE | c\nA -> bC\nB -> bD\nC -> c\nD -> c\nE -> ε");
+        let other = CFG::load_from_str("S -> aA | c\nA -> bC | bCE\nC -> c | cF\nE -> ε\nF -> fF");
+        let expected = CFG::load_from_str("S -> aA | c\nA -> bC\nC -> c").unwrap();
+        assert_eq!(cfg.unwrap().normalize(), expected);
+        assert_eq!(other.unwrap().normalize(), expected);
+
+        // the start symbol is kept
+        let cfg = CFG::load_from_str("S -> aT | b\nT -> aT | b").unwrap().normalize();
+        assert_eq!(cfg, CFG::load_from_str("S -> aS | b").unwrap());
+    }
+
+    #[test]
+    fn simplify_traced() {
+        let test_rules = "
+            S -> AB | a | C
+            A -> aA | ε
+            B -> bB
+            C -> c
+            D -> d
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let (simple, reports) = cfg.simplify_traced();
+        assert_eq!(simple, cfg.simplify());
+        let steps: Vec<SimplifyStep> = reports.iter().map(|x| x.step).collect();
+        assert_eq!(
+            steps,
+            vec![
+                SimplifyStep::Epsilon,
+                SimplifyStep::Unit,
+                SimplifyStep::Useless,
+                SimplifyStep::Unreachable,
+            ]
+        );
+        let rules = |x: &str| -> BTreeSet<Production> {
+            x.lines()
+                .flat_map(|x| CFG::parse_production(x, false).unwrap())
+                .collect()
+        };
+        assert_eq!(reports[0].removed, rules("A -> ε"));
+        assert_eq!(reports[0].added, rules("S -> B\nA -> a"));
+        assert_eq!(reports[1].removed, rules("S -> B\nS -> C"));
+        assert_eq!(reports[1].added, rules("S -> bB\nS -> c"));
+        assert_eq!(reports[2].removed, rules("S -> AB\nS -> bB\nB -> bB"));
+        assert!(reports[2].added.is_empty());
+        assert_eq!(reports[3].removed, rules("A -> a\nA -> aA\nC -> c\nD -> d"));
+        assert!(reports[3].added.is_empty());
+    }
+
+    #[test]
+    fn unicode_symbols() {
+        let test_rules = "
+            П -> 你Д好 | 🦀
+            Д -> дД | <名> | ε
+            <名> -> 字
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let nonterminals: Vec<String> = cfg.nonterminals().iter().map(|x| x.to_string()).collect();
+        assert_eq!(nonterminals, vec!["Д", "П", "<名>"]);
+        let terminals: String = cfg.terminals().iter().map(|x| x.symbol).collect();
+        assert_eq!(terminals, "д你好字🦀");
+        for input in vec!["🦀", "你好", "你дд好", "你д字好"] {
+            assert!(cfg.accepts_cyk(input), "Reject {}", input);
+        }
+        assert!(!cfg.accepts_cyk("你Д好"));
+        assert_eq!(CFG::load_from_str(&cfg.to_string()).unwrap(), cfg);
+
+        // an uncased letter is read as a Nonterminal by a classifier
+        let cjk = |ch: char| ch != '名' && !ch.is_uppercase();
+        let cfg = CFG::load_cfg_from_reader_with(Cursor::new("S -> a名\n名 -> b"), false, &cjk);
+        assert!(cfg.unwrap().accepts_cyk("ab"));
+    }
+
+    #[test]
+    fn enumerate() {
+        let strings = |cfg: &CFG, max_len| -> BTreeSet<String> {
+            cfg.enumerate(max_len).iter().map(|x| join(x, "")).collect()
+        };
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        assert_eq!(strings(&cfg, 4), vec!["aabb", "ab"].into_iter().map(String::from).collect());
+        let cfg = CFG::load_from_str("S -> SS | AS | a | ε\nA -> S").unwrap();
+        assert_eq!(strings(&cfg, 2), vec!["", "a", "aa"].into_iter().map(String::from).collect());
+        let cfg = CFG::load_from_str("S -> aS").unwrap();
+        assert!(cfg.enumerate(4).is_empty());
+    }
+
+    #[test]
+    fn display_empty() {
+        let cfg = CFG::new(Nonterminal::new("S".to_string(), 0), BTreeSet::new());
+        assert_eq!(format!("{}", cfg), "S -> \n");
+        assert_eq!(format!("{:#}", cfg), "S -> \n");
+    }
+
+    #[test]
+    fn normal_form_predicates() {
+        let cnf = CFG::load_from_str("S -> AB | a |\nA -> AB | a\nB -> b").unwrap();
+        assert!(cnf.is_in_cnf());
+        assert!(!cnf.is_in_gnf());
+        for test_rules in vec![
+            "S -> aB\nB -> b",
+            "S -> ABC\nA -> a\nB -> b\nC -> c",
+            "S -> A\nA -> a",
+            "S -> a\nA -> ε",
+            "S -> SS | a | ",
+        ] {
+            let cfg = CFG::load_from_str(test_rules).unwrap();
+            assert!(!cfg.is_in_cnf(), "{}", test_rules);
+        }
+
+        let gnf = CFG::load_from_str("S -> aSB | aB | \nB -> b").unwrap();
+        assert!(!gnf.is_in_gnf(), "S on the right side with 'S -> ε'");
+        let gnf = CFG::load_from_str("S -> aAB | aB | \nA -> aAB | aB\nB -> b").unwrap();
+        assert!(gnf.is_in_gnf());
+        assert!(!gnf.is_in_cnf());
+        for test_rules in vec!["S -> Ab\nA -> a", "S -> abA\nA -> a", "S -> a\nA -> ε"] {
+            let cfg = CFG::load_from_str(test_rules).unwrap();
+            assert!(!cfg.is_in_gnf(), "{}", test_rules);
+        }
+
+        let cfg = CFG::load("sample/cfg/simple-arithmetic.txt").unwrap();
+        assert!(!cfg.is_in_cnf() && !cfg.is_in_gnf());
+        assert!(cfg.to_cnf().is_in_cnf());
+        assert!(cfg.to_gnf().is_in_gnf());
+        let cfg = CFG::load("sample/cfg/brackets.txt").unwrap();
+        assert!(cfg.to_cnf().is_in_cnf());
+    }
+
+    #[test]
+    fn intersect_regular() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let generate = |cfg: CFG| -> Vec<String> {
+            Generator::new(cfg, 0, 8, true).unwrap().unique().strings().collect()
+        };
+        // a*b*
+        let table = "
+               | a | b
+            ^*A | A | B
+            *B | - | B
+        ";
+        let dfa = DFA::load_from_reader(Cursor::new(table), false).unwrap();
+        let intersection = cfg.intersect_regular(&dfa);
+        assert_eq!(generate(intersection), vec!["ab", "aabb", "aaabbb", "aaaabbbb"]);
+
+        // at most two 'a'
+        let table = "
+               | a | b
+            ^*A | B | A
+            *B | C | B
+            *C | - | C
+        ";
+        let dfa = DFA::load_from_reader(Cursor::new(table), false).unwrap();
+        let intersection = cfg.intersect_regular(&dfa);
+        assert_eq!(generate(intersection), vec!["ab", "aabb"]);
+
+        // the strings starting with 'b'
+        let table = "
+               | a | b
+            ^A | - | B
+            *B | B | B
+        ";
+        let dfa = DFA::load_from_reader(Cursor::new(table), false).unwrap();
+        let intersection = cfg.intersect_regular(&dfa);
+        assert!(intersection.productions.is_empty());
+        assert!(intersection.is_empty_language());
     }
 }
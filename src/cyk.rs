@@ -34,7 +34,7 @@ pub struct CYKParser {
 impl CYKParser {
     pub fn new(grammar: &cfg::CFG) -> CYKParser {
         CYKParser {
-            cfg: grammar.chomsky(),
+            cfg: grammar.to_cnf(),
         }
     }
     fn build_recognizer_table(&self, text: &str) -> CYKTable {
@@ -158,6 +158,26 @@ impl DFA {
         })
     }
 
+    pub fn start(&self) -> &State {
+        &self.start
+    }
+
+    pub fn is_accepting(&self, state: &State) -> bool {
+        self.finish.contains(state)
+    }
+
+    /// All states except the error state
+    pub fn states(&self) -> HashSet<&State> {
+        self.jump.keys().map(|x| &x.0).collect()
+    }
+
+    /// None if the symbol is not in the alphabet or the transition leads to the error state
+    pub fn next(&self, state: &State, symbol: char) -> Option<&State> {
+        self.jump
+            .get(&(state.clone(), symbol))
+            .filter(|x| !x.is_error())
+    }
+
     pub fn load(input_path: &str, debug: bool) -> io::Result<DFA> {
         let file = BufReader::new(File::open(input_path)?);
         DFA::load_from_reader(file, debug)
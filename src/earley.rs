@@ -62,6 +62,14 @@ impl<'er> State<'er> {
     }
 }
 
+/// Earley recognizer over the grammar as is,
+/// tolerates epsilon rules and left recursion
+pub fn recognize(grammar: &cfg::CFG, input: &str) -> bool {
+    let earley = EarleyParser::new(grammar);
+    let chart = earley.parse(input);
+    earley.accepts(&chart)
+}
+
 pub struct EarleyParser<'er> {
     cfg: &'er cfg::CFG,
 }
@@ -183,6 +191,16 @@ impl<'er> EarleyParser<'er> {
             }
         }
     }
+    /// Whether the whole input is derived from the start symbol
+    pub fn accepts(&self, chart: &Vec<Column<'er>>) -> bool {
+        match chart.last() {
+            Some(column) => column
+                .states
+                .iter()
+                .any(|s| s.rule.left == self.cfg.start && s.finished() && s.origin == 0),
+            None => false,
+        }
+    }
     pub fn print(&self, chart: &Vec<Column<'er>>) -> bool {
         let mut ret = false;
         let mut parsed = String::new();
@@ -252,3 +270,28 @@ impl<'er> EarleyParser<'er> {
     }
     */
 }
+
+#[cfg(test)]
+mod tests {
+    use self::super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn recognize() {
+        let cfg = cfg::CFG::load_from_reader(Cursor::new("E -> E+E | a")).unwrap();
+        assert!(super::recognize(&cfg, "a"));
+        assert!(super::recognize(&cfg, "a+a+a"));
+        assert!(!super::recognize(&cfg, "a+a+"));
+        assert!(!super::recognize(&cfg, ""));
+
+        let test_rules = "
+            S -> AaSbB |
+            A -> |
+            B -> A
+        ";
+        let cfg = cfg::CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        assert!(super::recognize(&cfg, ""));
+        assert!(super::recognize(&cfg, "aabb"));
+        assert!(!super::recognize(&cfg, "aab"));
+    }
+}
@@ -0,0 +1,497 @@
+use cfg::{CfgError, Nonterminal, Production, Symbol, Terminal, CFG};
+use itertools::join;
+use std::collections::BTreeSet;
+
+fn alternatives(grammar: &CFG) -> Vec<(Nonterminal, Vec<&Vec<Symbol>>)> {
+    let mut rules = grammar.rules_by_lhs();
+    // the start symbol goes first as in the Display of CFG
+    let mut result = Vec::new();
+    if let Some(start) = rules.remove(&grammar.start) {
+        result.push((grammar.start.clone(), start));
+    }
+    result.extend(rules);
+    result
+}
+
+fn name(nonterm: &Nonterminal) -> String {
+    if nonterm.sub_index > 0 {
+        format!("{}{}", nonterm.name, nonterm.sub_index)
+    } else {
+        nonterm.name.clone()
+    }
+}
+
+/// The bare word if parse_ebnf reads it back as a Nonterminal, the name in '<>' otherwise
+fn ebnf_name(nonterm: &Nonterminal) -> String {
+    let name = name(nonterm);
+    if name.chars().next().map_or(false, |x| x.is_uppercase())
+        && name.chars().all(|x| x.is_alphanumeric() || x == '_')
+    {
+        name
+    } else {
+        format!("<{}>", name)
+    }
+}
+
+fn quote(term: &Terminal) -> String {
+    if term.symbol == '"' {
+        "'\"'".to_string()
+    } else {
+        format!("\"{}\"", term.symbol)
+    }
+}
+
+/// Backus-Naur Form: '<S> ::= "a" <S> "b" | ""'
+pub fn to_bnf(grammar: &CFG) -> String {
+    let symbol = |x: &Symbol| match x {
+        &Symbol::N(ref n) => format!("<{}>", name(n)),
+        &Symbol::T(ref t) => quote(t),
+    };
+    let mut bnf = String::new();
+    for (left, alts) in alternatives(grammar) {
+        let alts = alts.iter().map(|alt| {
+            if alt.is_empty() {
+                "\"\"".to_string()
+            } else {
+                join(alt.iter().map(&symbol), " ")
+            }
+        });
+        bnf.push_str(&format!("<{}> ::= {}\n", name(&left), join(alts, " | ")));
+    }
+    bnf
+}
+
+fn ebnf_seq(seq: &[Symbol]) -> String {
+    join(
+        seq.iter().map(|x| match x {
+            &Symbol::N(ref n) => ebnf_name(n),
+            &Symbol::T(ref t) => quote(t),
+        }),
+        " , ",
+    )
+}
+
+/// The right side of an EBNF rule, the right recursion 'A -> wA | v' is folded
+/// into the repetition '{ w } , v' and the empty alternative into the option '[ v ]'
+fn ebnf_alternatives(left: &Nonterminal, alts: &[&Vec<Symbol>]) -> String {
+    let is_left = |x: &Symbol| x.is_eq_nonterm(left);
+    let mut repeated = Vec::new();
+    let mut rest = Vec::new();
+    let mut empty = false;
+    for alt in alts {
+        match alt.split_last() {
+            None => empty = true,
+            Some((last, body)) if is_left(last) && !body.is_empty() => {
+                repeated.push(ebnf_seq(body))
+            }
+            _ => rest.push(ebnf_seq(alt)),
+        }
+    }
+    if repeated.is_empty() && !empty {
+        return plain_alternatives(alts);
+    }
+    let rest = join(rest, " | ");
+    if repeated.is_empty() {
+        return if rest.is_empty() {
+            "\"\"".to_string()
+        } else {
+            format!("[ {} ]", rest)
+        };
+    }
+    let repeated = format!("{{ {} }}", join(repeated, " | "));
+    if rest.is_empty() {
+        if empty {
+            repeated
+        } else {
+            // 'A -> wA' alone derives nothing, it is not folded
+            plain_alternatives(alts)
+        }
+    } else if empty {
+        format!("{} , [ {} ]", repeated, rest)
+    } else if rest.contains(" | ") {
+        format!("{} , ( {} )", repeated, rest)
+    } else {
+        format!("{} , {}", repeated, rest)
+    }
+}
+
+fn plain_alternatives(alts: &[&Vec<Symbol>]) -> String {
+    join(
+        alts.iter().map(|alt| {
+            if alt.is_empty() {
+                "\"\"".to_string()
+            } else {
+                ebnf_seq(alt)
+            }
+        }),
+        " | ",
+    )
+}
+
+/// Extended Backus-Naur Form (ISO 14977 notation): 'S = "a" , { "b" } ;'
+pub fn to_ebnf(grammar: &CFG) -> String {
+    let mut ebnf = String::new();
+    for (left, alts) in alternatives(grammar) {
+        let rule = format!("{} = {} ;\n", ebnf_name(&left), ebnf_alternatives(&left, &alts));
+        ebnf.push_str(&rule);
+    }
+    ebnf
+}
+
+/// An element of the right side of an EBNF rule before the desugaring
+#[derive(Debug)]
+enum Item {
+    Symbol(Symbol),
+    Repeat(Vec<Vec<Item>>),
+    Optional(Vec<Vec<Item>>),
+    Group(Vec<Vec<Item>>),
+}
+
+struct RhsParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl RhsParser {
+    fn peek(&mut self) -> Option<char> {
+        while self.pos < self.chars.len()
+            && (self.chars[self.pos].is_whitespace() || self.chars[self.pos] == ',')
+        {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn alternatives(&mut self, close: Option<char>) -> Result<Vec<Vec<Item>>, ()> {
+        let mut alternatives = Vec::new();
+        loop {
+            alternatives.push(self.sequence()?);
+            match self.peek() {
+                Some('|') => self.pos += 1,
+                ch if ch == close => {
+                    self.pos += 1;
+                    return Ok(alternatives);
+                }
+                _ => return Err(()),
+            }
+        }
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Item>, ()> {
+        let mut items = Vec::new();
+        while let Some(ch) = self.peek() {
+            self.pos += 1;
+            match ch {
+                '|' | ')' | ']' | '}' => {
+                    self.pos -= 1;
+                    break;
+                }
+                '{' => items.push(Item::Repeat(self.alternatives(Some('}'))?)),
+                '[' => items.push(Item::Optional(self.alternatives(Some(']'))?)),
+                '(' => items.push(Item::Group(self.alternatives(Some(')'))?)),
+                '"' | '\'' => {
+                    let end = self.chars[self.pos..].iter().position(|&x| x == ch).ok_or(())?;
+                    for &t in &self.chars[self.pos..self.pos + end] {
+                        items.push(Item::Symbol(Symbol::T(Terminal::new(t))));
+                    }
+                    self.pos += end + 1;
+                }
+                '<' => {
+                    let end = self.chars[self.pos..].iter().position(|&x| x == '>').ok_or(())?;
+                    let name = self.chars[self.pos - 1..self.pos + end + 1].iter().collect();
+                    items.push(Item::Symbol(Symbol::N(Nonterminal::parse(name))));
+                    self.pos += end + 1;
+                }
+                'ε' => {}
+                _ if ch.is_uppercase() => {
+                    let mut name = ch.to_string();
+                    while let Some(&x) = self.chars.get(self.pos) {
+                        if !x.is_alphanumeric() && x != '_' {
+                            break;
+                        }
+                        name.push(x);
+                        self.pos += 1;
+                    }
+                    items.push(Item::Symbol(Symbol::N(Nonterminal::parse(name))));
+                }
+                _ => items.push(Item::Symbol(Symbol::new(ch.to_string()))),
+            }
+        }
+        Ok(items)
+    }
+}
+
+fn collect_nonterminals(alternatives: &[Vec<Item>], names: &mut BTreeSet<Nonterminal>) {
+    for item in alternatives.iter().flat_map(|x| x.iter()) {
+        match item {
+            &Item::Symbol(Symbol::N(ref n)) => {
+                names.insert(n.clone());
+            }
+            &Item::Symbol(Symbol::T(_)) => {}
+            &Item::Repeat(ref alts) | &Item::Optional(ref alts) | &Item::Group(ref alts) => {
+                collect_nonterminals(alts, names)
+            }
+        }
+    }
+}
+
+/// Replaces the EBNF operators by fresh Nonterminals named after the left side of the rule:
+/// '{ X }' by 'R -> XR | ε', '[ X ]' by 'O -> X | ε' and '( X )' by 'G -> X'
+struct Desugar {
+    taken: BTreeSet<Nonterminal>,
+    productions: BTreeSet<Production>,
+}
+
+impl Desugar {
+    fn fresh(&mut self, left: &Nonterminal) -> Nonterminal {
+        let mut idx = left.sub_index;
+        loop {
+            idx += 1;
+            let name = Nonterminal::new(left.name.clone(), idx);
+            if self.taken.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+
+    fn rule(&mut self, left: &Nonterminal, alternatives: Vec<Vec<Item>>) {
+        for alt in alternatives {
+            let right = self.sequence(left, alt);
+            self.productions.insert(Production::new(left.clone(), right));
+        }
+    }
+
+    fn sequence(&mut self, left: &Nonterminal, items: Vec<Item>) -> Vec<Symbol> {
+        let mut right = Vec::new();
+        for item in items {
+            match item {
+                Item::Symbol(s) => right.push(s),
+                Item::Group(mut alts) => {
+                    if alts.len() == 1 {
+                        let seq = self.sequence(left, alts.remove(0));
+                        right.extend(seq);
+                    } else {
+                        let group = self.fresh(left);
+                        self.rule(&group, alts);
+                        right.push(Symbol::N(group));
+                    }
+                }
+                Item::Optional(alts) => {
+                    let optional = self.fresh(left);
+                    self.rule(&optional, alts);
+                    self.productions.insert(Production::new(optional.clone(), vec![]));
+                    right.push(Symbol::N(optional));
+                }
+                Item::Repeat(alts) => {
+                    let repeat = self.fresh(left);
+                    for alt in alts {
+                        let mut seq = self.sequence(&repeat, alt);
+                        seq.push(Symbol::N(repeat.clone()));
+                        self.productions.insert(Production::new(repeat.clone(), seq));
+                    }
+                    self.productions.insert(Production::new(repeat.clone(), vec![]));
+                    right.push(Symbol::N(repeat));
+                }
+            }
+        }
+        right
+    }
+}
+
+/// Parses the grammar written in EBNF, one rule per line: 'S = a , { b } , [ c | "d" ] ;'.
+/// The concatenation commas and the final semicolon are optional, the terminals are
+/// the quoted strings or single characters that are not uppercase letters,
+/// the Nonterminals are the words starting with an uppercase letter or the names in '<>'
+pub fn parse_ebnf(input: &str) -> Result<CFG, CfgError> {
+    let mut rules = Vec::new();
+    for (idx, line) in input.lines().enumerate() {
+        let rule = line.trim();
+        if rule.is_empty() || rule.starts_with('#') {
+            continue;
+        }
+        let bad_rule = || CfgError::BadRule {
+            line: idx + 1,
+            text: line.to_string(),
+        };
+        let eq = rule.find('=').ok_or_else(&bad_rule)?;
+        let left = rule[..eq].trim();
+        let is_nonterminal = left.starts_with('<') && left.ends_with('>')
+            || left.chars().next().map_or(false, |x| x.is_uppercase())
+                && left.chars().all(|x| x.is_alphanumeric() || x == '_');
+        if left.is_empty() {
+            return Err(bad_rule());
+        } else if !is_nonterminal {
+            return Err(CfgError::TerminalOnLhs {
+                line: idx + 1,
+                text: line.to_string(),
+            });
+        }
+        let right = rule[eq + 1..].trim();
+        let right = if right.ends_with(';') {
+            &right[..right.len() - 1]
+        } else {
+            right
+        };
+        let mut parser = RhsParser {
+            chars: right.chars().collect(),
+            pos: 0,
+        };
+        let alternatives = parser.alternatives(None).map_err(|_| bad_rule())?;
+        rules.push((Nonterminal::parse(left.to_string()), alternatives));
+    }
+    if rules.is_empty() {
+        return Err(CfgError::NoRules);
+    }
+
+    let mut desugar = Desugar {
+        taken: BTreeSet::new(),
+        productions: BTreeSet::new(),
+    };
+    for &(ref left, ref alternatives) in &rules {
+        desugar.taken.insert(left.clone());
+        collect_nonterminals(alternatives, &mut desugar.taken);
+    }
+    let start = rules[0].0.clone();
+    for (left, alternatives) in rules {
+        desugar.rule(&left, alternatives);
+    }
+    Ok(CFG::new(start, desugar.productions))
+}
+
+#[cfg(test)]
+mod tests {
+    use self::super::*;
+    use generator::Generator;
+    use itertools::join;
+
+    #[test]
+    fn bnf() {
+        let cfg = CFG::load("sample/cfg/brackets.txt").unwrap();
+        assert_eq!(to_bnf(&cfg), "<S> ::= \"\" | <S> \"(\" <S> \")\" <S>\n");
+        let cfg = CFG::load("sample/cfg/simple-arithmetic.txt").unwrap();
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    r#"<E> ::= <E> "+" <T> | <T>"#,
+                    r#"<F> ::= <I> | "(" <E> ")""#,
+                    r#"<I> ::= <I> "0" | <I> "1" | <I> "a" | <I> "b" | "a" | "b""#,
+                    r#"<T> ::= <F> | <T> "*" <F>"#,
+                ],
+                "\n"
+            )
+        );
+        assert_eq!(to_bnf(&cfg), expected);
+        let cfg = CFG::load_from_str("<S2> -> \"<A1>").unwrap();
+        assert_eq!(cfg.to_bnf(), "<S2> ::= '\"' <A1>\n");
+    }
+
+    #[test]
+    fn ebnf() {
+        let test_rules = "
+            S -> aA | B
+            A -> bA | c
+            B -> aB | bB |
+            C -> dC | e | f
+            D -> a | ε
+            E -> aE
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    r#"S = B | "a" , A ;"#,
+                    r#"A = { "b" } , "c" ;"#,
+                    r#"B = { "a" | "b" } ;"#,
+                    r#"C = { "d" } , ( "e" | "f" ) ;"#,
+                    r#"D = [ "a" ] ;"#,
+                    r#"E = "a" , E ;"#,
+                ],
+                "\n"
+            )
+        );
+        assert_eq!(cfg.to_ebnf(), expected);
+        let cfg = CFG::load("sample/cfg/brackets.txt").unwrap();
+        assert_eq!(to_ebnf(&cfg), "S = { S , \"(\" , S , \")\" } ;\n");
+    }
+
+    #[test]
+    fn parse() {
+        let cfg = parse_ebnf("S = a { b } [ c ]").unwrap();
+        // the generator drops too long sentential forms, so the ε rules are removed
+        let generator = Generator::new(cfg.simplify(), 0, 3, true).unwrap().unique();
+        let strings: BTreeSet<String> = generator.strings().collect();
+        let expected: BTreeSet<String> = vec!["a", "ab", "ac", "abb", "abc"]
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+        assert_eq!(strings, expected);
+
+        let test_rules = "
+            # nested groups and the empty alternatives
+            Expr = Term , { ( '+' | \"-\" ) , Term } ;
+            Term = [ \"-\" ] ( x | \"(\" Expr \")\" | ) ;
+        ";
+        let cfg = parse_ebnf(test_rules).unwrap();
+        assert_eq!(cfg.start, Nonterminal::new("Expr".to_string(), 0));
+        for input in vec!["x", "", "-x+x", "(x-)", "-(x+-(x))-", "+"] {
+            assert!(cfg.accepts_cyk(input), "Reject {}", input);
+        }
+        for input in vec!["xx", "x(x)", "(x", "x)"] {
+            assert!(!cfg.accepts_cyk(input), "Accept {}", input);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for input in vec!["S = a { b", "S = a ] b", "S -> a", "S = \"a", "S = <A"] {
+            match parse_ebnf(input) {
+                Err(CfgError::BadRule { line: 1, .. }) => {}
+                other => panic!("{}: {:?}", input, other),
+            }
+        }
+        match parse_ebnf("S = a\na = b") {
+            Err(CfgError::TerminalOnLhs { line: 2, .. }) => {}
+            other => panic!("{:?}", other),
+        }
+        match parse_ebnf("# comment") {
+            Err(CfgError::NoRules) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let cfg = CFG::load("sample/cfg/simple-arithmetic.txt").unwrap();
+        let parsed = parse_ebnf(&cfg.to_ebnf()).unwrap();
+        for input in vec!["a", "a+b*a0", "(ab1+b)*b", "a*(b+a)"] {
+            assert!(parsed.accepts_cyk(input), "Reject {}", input);
+        }
+        for input in vec!["", "0", "a+", "(a", "a**b"] {
+            assert!(!parsed.accepts_cyk(input), "Accept {}", input);
+        }
+
+        // the names that are not bare words of parse_ebnf
+        let test_rules = "
+            S -> a<expr> | <Expr-list> | <A2>
+            <expr> -> b<expr> | c
+            <Expr-list> -> d<A2>
+            <A2> -> e
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let ebnf = cfg.to_ebnf();
+        assert!(ebnf.contains(r#""a" , <expr>"#), "{}", ebnf);
+        assert!(ebnf.contains("<Expr-list> = "), "{}", ebnf);
+        assert!(ebnf.contains("A2 = "), "{}", ebnf);
+        let parsed = parse_ebnf(&ebnf).unwrap();
+        // '{ "b" }' adds a fresh <expr1>, the names of the grammar are kept
+        assert!(cfg.nonterminals().is_subset(&parsed.nonterminals()));
+        for input in vec!["ac", "abbc", "de", "e"] {
+            assert!(parsed.accepts_cyk(input), "Reject {}", input);
+        }
+        for input in vec!["a", "abc b", "d", "ee"] {
+            assert!(!parsed.accepts_cyk(input), "Accept {}", input);
+        }
+    }
+}
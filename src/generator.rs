@@ -1,14 +1,69 @@
 use cfg;
-use std::collections::{HashMap, HashSet};
+use rand::Rng;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
 use std::fmt;
+use std::io::{self, Write};
+
+/// The order in which the sentential forms are expanded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// All short sequences come first, but the queue may grow large
+    BreadthFirst,
+    /// Needs less memory and reaches long sequences quickly,
+    /// sequences are not generated in the order of their length
+    DepthFirst,
+    /// The sequences are generated strictly in the non-decreasing order of their length:
+    /// the form with the shortest string it can derive is expanded first
+    LengthOrder,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GeneratorError {
+    /// The start symbol does not derive any terminal string,
+    /// so there is nothing to generate with any length bounds
+    EmptyLanguage,
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl Error for GeneratorError {
+    fn description(&self) -> &str {
+        match self {
+            &GeneratorError::EmptyLanguage => "The language of the grammar is empty",
+        }
+    }
+}
 
 pub struct Generator {
+    start: cfg::Nonterminal,
     left: bool,
     rules: HashMap<cfg::Symbol, Vec<Vec<cfg::Symbol>>>,
-    queue: HashSet<Vec<cfg::Symbol>>,
+    weights: HashMap<cfg::Symbol, Vec<f64>>,
+    // the length of the shortest string derived from each Nonterminal
+    min_lens: HashMap<cfg::Symbol, usize>,
+    // sentential forms to expand with the number of steps they are derived in,
+    // the index of the form they are derived from in 'parents' and their length bound
+    queue: VecDeque<(Vec<cfg::Symbol>, usize, Option<usize>, usize)>,
+    strategy: Strategy,
     visited: HashSet<Vec<cfg::Symbol>>,
+    // the sentential forms are not deduplicated, see Generator::canonical
+    canonical: bool,
+    emitted: Option<HashSet<Vec<cfg::Symbol>>>,
+    // the expanded sentential forms with the index of the one they are derived from,
+    // kept for Derivations only. The links are per queued form, not per sequence,
+    // so the canonical mode with the unit cycles can't make a loop of them
+    parents: Option<Vec<(Vec<cfg::Symbol>, Option<usize>)>>,
     min_len: usize,
     max_len: usize,
+    max_steps: Option<usize>,
+    max_queue: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -27,7 +82,22 @@ impl<'a> fmt::Display for GeneratedItem<'a> {
 }
 
 #[derive(Debug)]
-pub struct GeneratedSet(pub HashSet<Vec<cfg::Symbol>>);
+/// Displays the sequences in a stable sorted order
+pub struct GeneratedSet(pub BTreeSet<Vec<cfg::Symbol>>);
+
+impl GeneratedSet {
+    /// Takes up to 'limit' distinct sequences, the generator is not run further
+    pub fn from_generator(gen: Generator, limit: usize) -> GeneratedSet {
+        let mut set = BTreeSet::new();
+        for item in gen {
+            if set.len() >= limit {
+                break;
+            }
+            set.insert(item);
+        }
+        GeneratedSet(set)
+    }
+}
 
 impl fmt::Display for GeneratedSet {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -38,8 +108,33 @@ impl fmt::Display for GeneratedSet {
     }
 }
 
+#[derive(Debug)]
+pub struct GeneratedDerivation<'a>(pub &'a Vec<Vec<cfg::Symbol>>);
+
+impl<'a> fmt::Display for GeneratedDerivation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, item) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, " => ")?;
+            }
+            write!(f, "{}", GeneratedItem(item))?;
+        }
+        Ok(())
+    }
+}
+
 impl Generator {
-    pub fn new(grammar: cfg::CFG, lmin: u32, lmax: u32, left: bool) -> Generator {
+    /// Fails for the grammar of the empty language, so the empty output
+    /// always means that no string fits into the length bounds
+    pub fn new(
+        grammar: cfg::CFG,
+        lmin: u32,
+        lmax: u32,
+        left: bool,
+    ) -> Result<Generator, GeneratorError> {
+        if grammar.is_empty_language() {
+            return Err(GeneratorError::EmptyLanguage);
+        }
         let mut rules: HashMap<cfg::Symbol, Vec<Vec<cfg::Symbol>>> = HashMap::new();
         for rule in grammar.productions {
             let mut symbols = match rules.get(&cfg::Symbol::N(rule.left.clone())) {
@@ -49,35 +144,328 @@ impl Generator {
             symbols.push(rule.right.clone());
             rules.insert(cfg::Symbol::N(rule.left.clone()), symbols);
         }
-        let mut queue = HashSet::new();
-        for cases in rules.get(&cfg::Symbol::N(grammar.start)) {
-            for case in cases {
-                queue.insert(case.clone());
-            }
-        }
-        Generator {
+        let min_lens = shortest_lengths(&rules);
+        let mut generator = Generator {
+            start: grammar.start,
             left: left,
             rules: rules,
-            queue: queue,
+            weights: HashMap::new(),
+            min_lens: min_lens,
+            queue: VecDeque::new(),
+            strategy: Strategy::BreadthFirst,
             visited: HashSet::new(),
+            canonical: false,
+            emitted: None,
+            parents: None,
             min_len: lmin as usize,
             max_len: lmax as usize,
+            max_steps: None,
+            max_queue: None,
+        };
+        generator.restart();
+        Ok(generator)
+    }
+
+    /// Start generating from the start symbol again with the new length bounds,
+    /// the rules and the other settings are kept
+    pub fn set_bounds(&mut self, min: usize, max: usize) {
+        self.min_len = min;
+        self.max_len = max;
+        self.restart();
+    }
+
+    fn restart(&mut self) {
+        self.queue.clear();
+        self.visited.clear();
+        if let Some(cases) = self.rules.get(&cfg::Symbol::N(self.start.clone())) {
+            for case in cases {
+                let bound = self.length_bound(case);
+                self.queue.push_back((case.clone(), 1, None, bound));
+                self.visited.insert(case.clone());
+            }
+        }
+        self.sort_queue();
+        if let Some(ref mut emitted) = self.emitted {
+            emitted.clear();
+        }
+        if let Some(ref mut parents) = self.parents {
+            parents.clear();
         }
     }
-}
 
-impl Iterator for Generator {
-    type Item = Vec<cfg::Symbol>;
+    /// Skip the sequences that have already been generated by another derivation,
+    /// all generated sequences are kept in memory for that
+    pub fn unique(mut self) -> Generator {
+        self.emitted = Some(HashSet::new());
+        self
+    }
 
-    fn next(&mut self) -> Option<Vec<cfg::Symbol>> {
+    /// Expand only the leftmost Nonterminal and keep every sentential form, even if it has been
+    /// derived another way, so a sequence is yielded once per its leftmost derivation
+    /// (among the derivations that fit into the length bound). That makes the generator
+    /// a probe for ambiguity, but the number of forms grows quickly for ambiguous grammars.
+    /// The iterator does not end for a grammar with a unit or ε cycle like 'S -> A | a',
+    /// 'A -> S': every pass of the cycle is one more derivation, so bound the derivations
+    /// with Generator::max_steps before collecting the sequences of such a grammar
+    pub fn canonical(mut self) -> Generator {
+        self.left = true;
+        self.canonical = true;
+        self.emitted = None;
+        self
+    }
+
+    /// Sets the weights of the productions for the random derivation,
+    /// a production missing from the map has weight 1.0
+    pub fn with_weights(mut self, weights: HashMap<cfg::Production, f64>) -> Generator {
+        self.weights = self.rules
+            .iter()
+            .map(|(left, rules)| {
+                let left = left.as_nonterminal().unwrap();
+                let rule_weights = rules
+                    .iter()
+                    .map(|right| {
+                        let prod = cfg::Production::new(left.clone(), right.clone());
+                        weights.get(&prod).cloned().unwrap_or(1.0)
+                    })
+                    .collect();
+                (cfg::Symbol::N(left.clone()), rule_weights)
+            })
+            .collect();
+        self
+    }
+
+    fn choose<R: Rng>(&self, rng: &mut R, left: &cfg::Symbol, count: usize) -> usize {
+        if let Some(weights) = self.weights.get(left) {
+            let total: f64 = weights.iter().sum();
+            if total > 0.0 {
+                let mut point = rng.gen_range(0.0, total);
+                for (idx, weight) in weights.iter().enumerate() {
+                    if point < *weight {
+                        return idx;
+                    }
+                    point -= *weight;
+                }
+                // rounding errors, take the last production with a positive weight
+                return weights.iter().rposition(|x| *x > 0.0).unwrap();
+            }
+        }
+        rng.gen_range(0, count)
+    }
+
+    /// Makes a random leftmost derivation from the start symbol, the production
+    /// for a Nonterminal is chosen uniformly or according to the weights,
+    /// returns None if the derivation does not end in 'max_steps' expansions
+    pub fn sample<R: Rng>(&self, rng: &mut R, max_steps: usize) -> Option<Vec<cfg::Symbol>> {
+        let mut item = vec![cfg::Symbol::N(self.start.clone())];
+        for _ in 0..max_steps {
+            let idx = match item.iter().position(|x| x.is_nonterminal()) {
+                Some(idx) => idx,
+                None => return Some(item),
+            };
+            let rules = match self.rules.get(&item[idx]) {
+                Some(rules) => rules,
+                // a Nonterminal without rules does not derive anything
+                None => return None,
+            };
+            let seq = &rules[self.choose(rng, &item[idx], rules.len())];
+            item.splice(idx..idx + 1, seq.iter().cloned());
+        }
+        if item.iter().all(|x| x.is_terminal()) {
+            Some(item)
+        } else {
+            None
+        }
+    }
+
+    /// Yield the generated sequences as strings
+    pub fn strings(self) -> impl Iterator<Item = String> {
+        self.map(|x| GeneratedItem(&x).to_string())
+    }
+
+    /// Only the strings accepted by the predicate
+    pub fn filter_strings<F: Fn(&str) -> bool>(self, f: F) -> impl Iterator<Item = String> {
+        self.strings().filter(move |x| f(x))
+    }
+
+    /// Write the generated sequences one per line without collecting them,
+    /// the writer is flushed every 1024 lines and at the end
+    pub fn write_to<W: Write>(self, w: &mut W) -> io::Result<()> {
+        for (idx, seq) in self.enumerate() {
+            write!(w, "{}\n", GeneratedItem(&seq))?;
+            if (idx + 1) % 1024 == 0 {
+                w.flush()?;
+            }
+        }
+        w.flush()
+    }
+
+    /// Collect all the sequences expanding the breadth-first frontier in parallel,
+    /// the frontier of every step is split across the threads and the derived forms are
+    /// deduplicated while merging, the strategy, the queue limit and the derivations
+    /// are not used here
+    #[cfg(feature = "rayon")]
+    pub fn par_collect(self) -> HashSet<Vec<cfg::Symbol>> {
+        let mut result = HashSet::new();
+        let mut visited = self.visited;
+        let (rules, left, min_len, max_len) = (&self.rules, self.left, self.min_len, self.max_len);
+        // the alternatives of the start symbol are queued without the length check
+        let mut frontier: Vec<Vec<cfg::Symbol>> = self
+            .queue
+            .into_iter()
+            .map(|x| x.0)
+            .filter(|x| x.len() <= max_len)
+            .collect();
+        let mut steps = 1;
+        while !frontier.is_empty() {
+            let expand_forms = self.max_steps.map_or(true, |max| steps < max);
+            let (done, derived) = frontier
+                .par_iter()
+                .map(|form| {
+                    let (mut done, mut derived) = (Vec::new(), Vec::new());
+                    let idx = if left {
+                        form.iter().position(|x| x.is_nonterminal())
+                    } else {
+                        form.iter().rposition(|x| x.is_nonterminal())
+                    };
+                    match idx {
+                        None if form.is_empty() || form.len() >= min_len => done.push(form.clone()),
+                        None => {}
+                        Some(idx) if expand_forms => {
+                            for seq in rules.get(&form[idx]).into_iter().flat_map(|x| x.iter()) {
+                                if form.len() - 1 + seq.len() <= max_len {
+                                    let mut new_seq = form[..idx].to_vec();
+                                    new_seq.extend(seq.iter().cloned());
+                                    new_seq.extend(form[idx + 1..].iter().cloned());
+                                    derived.push(new_seq);
+                                }
+                            }
+                        }
+                        Some(_) => {}
+                    }
+                    (done, derived)
+                }).reduce(
+                    || (Vec::new(), Vec::new()),
+                    |mut a, b| {
+                        a.0.extend(b.0);
+                        a.1.extend(b.1);
+                        a
+                    },
+                );
+            result.extend(done);
+            frontier = derived.into_iter().filter(|x| visited.insert(x.clone())).collect();
+            steps += 1;
+        }
+        result
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy) -> Generator {
+        self.strategy = strategy;
+        self.sort_queue();
+        self
+    }
+
+    /// The LengthOrder strategy inserts the derived forms into the queue sorted by the bound
+    fn sort_queue(&mut self) {
+        if self.strategy == Strategy::LengthOrder {
+            let mut queue: Vec<_> = self.queue.drain(..).collect();
+            queue.sort_by_key(|x| x.3);
+            self.queue.extend(queue);
+        }
+    }
+
+    /// The length of the shortest string the sentential form derives,
+    /// it never decreases along a derivation
+    fn length_bound(&self, form: &[cfg::Symbol]) -> usize {
+        form.iter().fold(0, |acc, x| {
+            let len = if x.is_terminal() {
+                1
+            } else {
+                self.min_lens.get(x).cloned().unwrap_or(usize::MAX)
+            };
+            acc.saturating_add(len)
+        })
+    }
+
+    /// Drop the sentential forms derived in more than 'steps' steps,
+    /// this bounds the time and memory for grammars like 'S -> SS | a'
+    pub fn max_steps(mut self, steps: usize) -> Generator {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    /// Keep at most 'size' sentential forms in the queue, the forms deriving
+    /// the longest strings are dropped first, so the short strings are still generated
+    pub fn max_queue(mut self, size: usize) -> Generator {
+        self.max_queue = Some(size);
+        self
+    }
+
+    fn shrink_queue(&mut self) {
+        let excess = match self.max_queue {
+            Some(size) if self.queue.len() > size => self.queue.len() - size,
+            _ => return,
+        };
+        // the forms with the largest bound are dropped in one pass,
+        // the later ones in the queue first among the equal bounds
+        let mut order: Vec<(usize, usize)> = self
+            .queue
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| (item.3, idx))
+            .collect();
+        let keep = order.len() - excess;
+        order.select_nth_unstable(keep);
+        let mut dropped = vec![false; order.len()];
+        for &(_, idx) in &order[keep..] {
+            dropped[idx] = true;
+        }
+        let mut idx = 0;
+        self.queue.retain(|_| {
+            idx += 1;
+            !dropped[idx - 1]
+        });
+    }
+
+    /// Yield every generated sequence along with its derivation:
+    /// the sentential forms from the start symbol down to the sequence
+    pub fn derivations(mut self) -> Derivations {
+        self.parents = Some(Vec::new());
+        Derivations(self)
+    }
+
+    fn trace(&self, item: &Vec<cfg::Symbol>, parent: Option<usize>) -> Vec<Vec<cfg::Symbol>> {
+        let mut trace = vec![item.clone()];
+        if let Some(ref parents) = self.parents {
+            let mut current = parent;
+            while let Some(idx) = current {
+                trace.push(parents[idx].0.clone());
+                current = parents[idx].1;
+            }
+        }
+        trace.push(vec![cfg::Symbol::N(self.start.clone())]);
+        trace.reverse();
+        trace
+    }
+
+    fn is_new(&mut self, item: &Vec<cfg::Symbol>) -> bool {
+        match self.emitted {
+            Some(ref mut emitted) => emitted.insert(item.clone()),
+            None => true,
+        }
+    }
+
+    /// The next sequence with the index of the form it is derived from
+    fn next_with_parent(&mut self) -> Option<(Vec<cfg::Symbol>, Option<usize>)> {
         loop {
-            let next_item = match self.queue.iter().next() {
-                Some(item) => item.to_vec(),
+            let (next_item, steps, parent, _) = match self.queue.pop_front() {
+                Some(item) => item,
                 None => return None,
             };
-            self.queue.remove(&next_item);
             if next_item.is_empty() {
-                return Some(next_item);
+                if self.is_new(&next_item) {
+                    return Some((next_item, parent));
+                }
+                continue;
             }
             if next_item.len() > self.max_len {
                 // too long a sequence, drop it
@@ -85,10 +473,10 @@ impl Iterator for Generator {
             }
             if next_item.iter().all(|x| x.is_terminal()) {
                 // only terminals
-                if next_item.len() >= self.min_len {
-                    return Some(next_item);
+                if next_item.len() >= self.min_len && self.is_new(&next_item) {
+                    return Some((next_item, parent));
                 } else {
-                    // too short a sequence, drop
+                    // too short or already generated sequence, drop
                     continue;
                 }
             }
@@ -97,7 +485,20 @@ impl Iterator for Generator {
             } else {
                 next_item.iter().rposition(|x| x.is_nonterminal()).unwrap()
             };
+            if self.max_steps.map_or(false, |max| steps >= max) {
+                // the step budget is spent, drop it
+                continue;
+            }
+            // a Nonterminal without rules can't be completed, such a form is dropped
             if let Some(rules) = self.rules.get(&next_item[idx]) {
+                let link = match self.parents {
+                    Some(ref mut parents) => {
+                        parents.push((next_item.clone(), parent));
+                        Some(parents.len() - 1)
+                    }
+                    None => None,
+                };
+                let mut derived = Vec::new();
                 for seq in rules {
                     let mut new_seq = next_item[..idx].to_vec();
                     new_seq.extend(seq.clone());
@@ -105,15 +506,431 @@ impl Iterator for Generator {
                         new_seq.extend(next_item[idx + 1..].iter().cloned());
                     }
                     if new_seq.len() <= self.max_len {
-                        if !self.visited.contains(&new_seq) {
+                        if self.canonical || !self.visited.contains(&new_seq) {
                             self.visited.insert(new_seq.clone());
-                            self.queue.insert(new_seq);
+                            let bound = self.length_bound(&new_seq);
+                            derived.push((new_seq, steps + 1, link, bound));
                         }
                     }
                 }
-            } else {
-                unreachable!() // unreachable Nonterminal symbol ???
+                match self.strategy {
+                    Strategy::BreadthFirst => self.queue.extend(derived),
+                    Strategy::DepthFirst => {
+                        for item in derived.into_iter().rev() {
+                            self.queue.push_front(item);
+                        }
+                    }
+                    Strategy::LengthOrder => {
+                        // the queue is kept sorted by the bound, the equal ones in FIFO order
+                        for item in derived {
+                            let pos = self.queue.partition_point(|x| x.3 <= item.3);
+                            self.queue.insert(pos, item);
+                        }
+                    }
+                }
+                self.shrink_queue();
+            }
+        }
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Vec<cfg::Symbol>;
+
+    fn next(&mut self) -> Option<Vec<cfg::Symbol>> {
+        self.next_with_parent().map(|x| x.0)
+    }
+}
+
+/// The shortest string length of every Nonterminal,
+/// the Nonterminals deriving no terminal string are missing
+fn shortest_lengths(
+    rules: &HashMap<cfg::Symbol, Vec<Vec<cfg::Symbol>>>,
+) -> HashMap<cfg::Symbol, usize> {
+    let mut lens: HashMap<cfg::Symbol, usize> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (left, alternatives) in rules {
+            for right in alternatives {
+                let len = right.iter().fold(Some(0), |acc, x| {
+                    let len = if x.is_terminal() {
+                        Some(1)
+                    } else {
+                        lens.get(x).cloned()
+                    };
+                    acc.and_then(|acc| len.map(|len| acc + len))
+                });
+                if let Some(len) = len {
+                    if lens.get(left).map_or(true, |&old| len < old) {
+                        lens.insert(left.clone(), len);
+                        changed = true;
+                    }
+                }
             }
         }
     }
+    lens
+}
+
+pub struct Derivations(Generator);
+
+impl Iterator for Derivations {
+    type Item = (Vec<cfg::Symbol>, Vec<Vec<cfg::Symbol>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_with_parent().map(|(item, parent)| {
+            let trace = self.0.trace(&item, parent);
+            (item, trace)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use self::super::*;
+    use cfg::{Production, CFG};
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn unique() {
+        let cfg = CFG::load_from_str("S -> A | a\nA -> a").unwrap();
+        let generated: Vec<Vec<cfg::Symbol>> = Generator::new(cfg, 0, 4, true)
+            .unwrap()
+            .unique()
+            .collect();
+        assert_eq!(generated, vec![CFG::parse_rhs("a").unwrap()]);
+    }
+
+    #[test]
+    fn sample() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let gen = Generator::new(cfg, 0, 0, true).unwrap();
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut samples = Vec::new();
+        for _ in 0..20 {
+            if let Some(item) = gen.sample(&mut rng, 10) {
+                let n = item.len() / 2;
+                assert!(item[..n].iter().all(|x| x.is_eq_term('a')));
+                assert!(item[n..].iter().all(|x| x.is_eq_term('b')));
+                samples.push(item);
+            }
+        }
+        assert!(!samples.is_empty());
+        let mut rng = StdRng::seed_from_u64(42);
+        let again: Vec<_> = (0..20).filter_map(|_| gen.sample(&mut rng, 10)).collect();
+        assert_eq!(again, samples);
+
+        let cfg = CFG::load_from_str("S -> SS").unwrap();
+        assert!(Generator::new(cfg, 0, 0, true).is_err());
+        let cfg = CFG::load_from_str("S -> aS | b").unwrap();
+        let gen = Generator::new(cfg, 0, 0, true).unwrap();
+        assert_eq!(gen.sample(&mut rng, 0), None);
+    }
+
+    #[test]
+    fn weighted_sample() {
+        let cfg = CFG::load_from_str("S -> a | b").unwrap();
+        let mut weights = HashMap::new();
+        weights.insert(Production::new(cfg.start.clone(), CFG::parse_rhs("a").unwrap()), 3.0);
+        let gen = Generator::new(cfg, 0, 0, true).unwrap().with_weights(weights);
+        let mut rng = StdRng::seed_from_u64(7);
+        let count = (0..4000)
+            .filter(|_| gen.sample(&mut rng, 1).unwrap()[0].is_eq_term('a'))
+            .count();
+        // 'a' has weight 3.0 and 'b' has the default 1.0
+        assert!(count > 2800 && count < 3200, "{} of 4000", count);
+    }
+
+    #[test]
+    fn max_steps() {
+        let cfg = CFG::load_from_str("S -> SS | a").unwrap();
+        let generated: HashSet<Vec<cfg::Symbol>> =
+            Generator::new(cfg, 0, 1000, true).unwrap().max_steps(7).collect();
+        // 'a' repeated n times needs 2n - 1 steps
+        let expected: HashSet<Vec<cfg::Symbol>> = vec!["a", "aa", "aaa", "aaaa"]
+            .into_iter()
+            .map(|x| CFG::parse_rhs(x).unwrap())
+            .collect();
+        assert_eq!(generated, expected);
+    }
+
+    #[test]
+    fn derivations() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let mut generated: Vec<String> = Generator::new(cfg, 0, 6, true).unwrap()
+            .derivations()
+            .map(|(item, trace)| {
+                assert_eq!(trace.last(), Some(&item));
+                format!("{}", GeneratedDerivation(&trace))
+            })
+            .collect();
+        generated.sort();
+        assert_eq!(
+            generated,
+            vec![
+                "S => aSb => aaSbb => aaabbb",
+                "S => aSb => aabb",
+                "S => ab",
+            ]
+        );
+    }
+
+    #[test]
+    fn strategy() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let expected: Vec<Vec<cfg::Symbol>> = vec!["ab", "aabb", "aaabbb"]
+            .into_iter()
+            .map(|x| CFG::parse_rhs(x).unwrap())
+            .collect();
+        let generated: Vec<Vec<cfg::Symbol>> = Generator::new(cfg, 0, 6, true).unwrap().collect();
+        assert_eq!(generated, expected);
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let generated: Vec<Vec<cfg::Symbol>> = Generator::new(cfg, 0, 6, true).unwrap()
+            .with_strategy(Strategy::DepthFirst)
+            .collect();
+        assert_eq!(generated, expected.into_iter().rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn length_order() {
+        let cfg = CFG::load_from_str("S -> aaaa | A | SB\nA -> b | cA\nB -> ε | d").unwrap();
+        // the breadth-first order gives a long string before the short ones
+        let generated: Vec<String> = Generator::new(cfg, 0, 4, true).unwrap().strings().collect();
+        assert_eq!(generated[..2], ["aaaa".to_string(), "b".to_string()]);
+
+        let cfg = CFG::load_from_str("S -> aaaa | A | SB\nA -> b | cA\nB -> ε | d").unwrap();
+        let generated: Vec<String> = Generator::new(cfg, 0, 4, true)
+            .unwrap()
+            .unique()
+            .with_strategy(Strategy::LengthOrder)
+            .strings()
+            .collect();
+        assert!(generated.windows(2).all(|x| x[0].len() <= x[1].len()));
+        assert_eq!(generated[..3], ["b", "bd", "cb"]);
+        assert_eq!(generated.len(), 11);
+
+        // the queue is sorted again after the restart with the new bounds
+        let cfg = CFG::load_from_str("S -> aaaa | b").unwrap();
+        let mut gen = Generator::new(cfg, 0, 4, true)
+            .unwrap()
+            .with_strategy(Strategy::LengthOrder);
+        let generated: Vec<String> = gen.by_ref().map(|x| GeneratedItem(&x).to_string()).collect();
+        assert_eq!(generated, vec!["b", "aaaa"]);
+        gen.set_bounds(0, 4);
+        assert_eq!(gen.strings().collect::<Vec<_>>(), vec!["b", "aaaa"]);
+    }
+
+    #[test]
+    fn max_queue() {
+        let cfg = CFG::load_from_str("S -> SS | SaS | a | b").unwrap();
+        let mut gen = Generator::new(cfg, 0, 7, true).unwrap().unique().max_queue(8);
+        let mut generated = Vec::new();
+        while let Some(item) = gen.next() {
+            assert!(gen.queue.len() <= 8);
+            generated.push(GeneratedItem(&item).to_string());
+        }
+        assert!(generated.contains(&"a".to_string()));
+        assert!(generated.contains(&"b".to_string()));
+        assert!(generated.contains(&"ab".to_string()));
+    }
+
+    #[test]
+    fn strings() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let generated: Vec<String> = Generator::new(cfg, 0, 6, true).unwrap().strings().collect();
+        assert_eq!(generated, vec!["ab", "aabb", "aaabbb"]);
+    }
+
+    #[test]
+    fn generated_set_from_generator() {
+        let cfg = CFG::load_from_str("S -> aS | Sa | b").unwrap();
+        let gen = Generator::new(cfg, 0, 20, true).unwrap();
+        let set = GeneratedSet::from_generator(gen, 5);
+        assert_eq!(set.0.len(), 5);
+        assert_eq!(format!("{}", set), "ab\naba\nb\nba\nbaa\n");
+
+        let cfg = CFG::load_from_str("S -> a | b").unwrap();
+        let gen = Generator::new(cfg, 0, 20, true).unwrap();
+        assert_eq!(GeneratedSet::from_generator(gen, 5).0.len(), 2);
+    }
+
+    #[test]
+    fn filter_strings() {
+        let cfg = CFG::load_from_str("S -> aS | a").unwrap();
+        let generated: Vec<String> = Generator::new(cfg, 0, 6, true)
+            .unwrap()
+            .filter_strings(|x| x.len() % 2 == 0)
+            .collect();
+        assert_eq!(generated, vec!["aa", "aaaa", "aaaaaa"]);
+        let cfg = CFG::load_from_str("S -> aS | bS | b").unwrap();
+        let count = Generator::new(cfg, 0, 4, true)
+            .unwrap()
+            .filter_strings(|x| x.matches('b').count() >= 2)
+            .count();
+        // the strings ending with 'b' with one more 'b' before it
+        assert_eq!(count, 1 + 3 + 7);
+    }
+
+    #[test]
+    fn generated_set_is_sorted() {
+        let cfg = CFG::load_from_str("S -> bS | aS | a").unwrap();
+        let set = GeneratedSet(Generator::new(cfg, 0, 2, true).unwrap().collect());
+        assert_eq!(format!("{}", set), "a\naa\nba\n");
+    }
+
+    #[test]
+    fn undefined_nonterminal() {
+        let cfg = CFG::load_from_str("S -> aB | A | a\nA -> bA | b").unwrap();
+        let generated: Vec<String> = Generator::new(cfg, 0, 3, true)
+            .unwrap()
+            .unique()
+            .strings()
+            .collect();
+        assert_eq!(generated, vec!["a", "b", "bb", "bbb"]);
+        let cfg = CFG::load_from_str("S -> AB").unwrap();
+        assert_eq!(Generator::new(cfg, 0, 3, false).err(), Some(GeneratorError::EmptyLanguage));
+    }
+
+    #[test]
+    fn write_to() {
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        Generator::new(cfg, 0, 6, true).unwrap().write_to(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "ab\naabb\naaabbb\n");
+        let cfg = CFG::load_from_str("S -> aS | bS |").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        Generator::new(cfg, 0, 11, true).unwrap().unique().write_to(&mut out).unwrap();
+        // all the strings up to the length 10 over {a, b}, more than the flush period
+        assert_eq!(out.iter().filter(|&&x| x == b'\n').count(), 2047);
+    }
+
+    #[test]
+    fn canonical() {
+        let cfg = CFG::load_from_str("S -> S+S | a").unwrap();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for seq in Generator::new(cfg, 0, 7, false).unwrap().canonical().strings() {
+            *counts.entry(seq).or_insert(0) += 1;
+        }
+        // the Catalan numbers
+        assert_eq!(counts["a"], 1);
+        assert_eq!(counts["a+a"], 1);
+        assert_eq!(counts["a+a+a"], 2);
+        assert_eq!(counts["a+a+a+a"], 5);
+
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let generator = Generator::new(cfg, 0, 6, true).unwrap().canonical();
+        let generated: Vec<String> = generator.strings().collect();
+        assert_eq!(generated, vec!["ab", "aabb", "aaabbb"]);
+    }
+
+    #[test]
+    fn canonical_derivations() {
+        // every pass of the unit cycle is a separate derivation of 'a'
+        let cfg = CFG::load_from_str("S -> A | a\nA -> S").unwrap();
+        let generated: Vec<String> = Generator::new(cfg, 0, 1, true)
+            .unwrap()
+            .canonical()
+            .max_steps(5)
+            .derivations()
+            .map(|(_, trace)| format!("{}", GeneratedDerivation(&trace)))
+            .collect();
+        assert_eq!(
+            generated,
+            vec!["S => a", "S => A => S => a", "S => A => S => A => S => a"]
+        );
+
+        let cfg = CFG::load_from_str("S -> S+S | a").unwrap();
+        let mut generated: Vec<String> = Generator::new(cfg, 0, 5, true)
+            .unwrap()
+            .canonical()
+            .derivations()
+            .filter(|x| x.0.len() == 5)
+            .map(|(_, trace)| format!("{}", GeneratedDerivation(&trace)))
+            .collect();
+        generated.sort();
+        assert_eq!(
+            generated,
+            vec![
+                "S => S+S => S+S+S => a+S+S => a+a+S => a+a+a",
+                "S => S+S => a+S => a+S+S => a+a+S => a+a+a",
+            ]
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_collect() {
+        for test_rules in vec!["S -> aSb | ab", "S -> SS | (S) |", "S -> aSA | b\nA -> a | Ab"] {
+            let cfg = CFG::load_from_str(test_rules).unwrap();
+            let expected: HashSet<Vec<cfg::Symbol>> = Generator::new(cfg, 1, 10, true)
+                .unwrap()
+                .collect();
+            let cfg = CFG::load_from_str(test_rules).unwrap();
+            let generated = Generator::new(cfg, 1, 10, true).unwrap().par_collect();
+            assert_eq!(generated, expected, "{}", test_rules);
+        }
+        let cfg = CFG::load_from_str("S -> SS | a").unwrap();
+        let generator = Generator::new(cfg, 0, 8, false).unwrap().max_steps(4);
+        let expected: HashSet<Vec<cfg::Symbol>> = generator.collect();
+        let cfg = CFG::load_from_str("S -> SS | a").unwrap();
+        let generated = Generator::new(cfg, 0, 8, false).unwrap().max_steps(4).par_collect();
+        assert_eq!(generated, expected);
+
+        // a terminal alternative of the start symbol longer than the bound
+        let cfg = CFG::load_from_str("S -> aaaa | aA\nA -> b").unwrap();
+        let generated = Generator::new(cfg, 0, 2, true).unwrap().par_collect();
+        let expected: HashSet<Vec<cfg::Symbol>> =
+            vec![CFG::parse_rhs("ab").unwrap()].into_iter().collect();
+        assert_eq!(generated, expected);
+    }
+
+    // cargo test --release --features rayon par_collect_timing -- --ignored --nocapture
+    #[cfg(feature = "rayon")]
+    #[test]
+    #[ignore]
+    fn par_collect_timing() {
+        use std::time::Instant;
+        let test_rules = "S -> SS | (S) | [S] | a |";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let now = Instant::now();
+        let expected: HashSet<Vec<cfg::Symbol>> = Generator::new(cfg, 0, 12, true)
+            .unwrap()
+            .collect();
+        let collect_time = now.elapsed();
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        let now = Instant::now();
+        let generated = Generator::new(cfg, 0, 12, true).unwrap().par_collect();
+        let par_time = now.elapsed();
+        assert_eq!(generated, expected);
+        println!(
+            "{} sequences, collect: {:?}, par_collect: {:?}",
+            expected.len(),
+            collect_time,
+            par_time
+        );
+    }
+
+    #[test]
+    fn empty_language() {
+        let cfg = CFG::load_from_str("S -> aS | A\nA -> Ab").unwrap();
+        let err = Generator::new(cfg, 0, 8, true).err().unwrap();
+        assert_eq!(err.to_string(), "The language of the grammar is empty");
+        // no string fits into the bounds, but the language is not empty
+        let cfg = CFG::load_from_str("S -> aaaa").unwrap();
+        assert_eq!(Generator::new(cfg, 0, 3, true).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn set_bounds() {
+        let cfg = CFG::load_from_str("S -> aS | b").unwrap();
+        let mut gen = Generator::new(cfg, 0, 3, true).unwrap().unique();
+        let generated: Vec<String> = gen.by_ref().map(|x| GeneratedItem(&x).to_string()).collect();
+        assert_eq!(generated, vec!["b", "ab", "aab"]);
+        gen.set_bounds(4, 5);
+        let generated: Vec<String> = gen.by_ref().map(|x| GeneratedItem(&x).to_string()).collect();
+        assert_eq!(generated, vec!["aaab", "aaaab"]);
+        gen.set_bounds(0, 2);
+        assert_eq!(gen.strings().collect::<Vec<_>>(), vec!["b", "ab"]);
+    }
 }
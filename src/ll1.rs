@@ -0,0 +1,124 @@
+use cfg::{Lookahead, Nonterminal, Production, CFG};
+use itertools::join;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A cell of the LL(1) table claimed by more than one production
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub left: Nonterminal,
+    pub lookahead: Lookahead,
+    pub productions: Vec<Production>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Conflict at ({}, {}): {}",
+            self.left,
+            self.lookahead,
+            join(
+                self.productions
+                    .iter()
+                    .map(|x| format!("{} -> {}", x.left, join(&x.right, ""))),
+                " / "
+            )
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Ll1Table {
+    table: HashMap<(Nonterminal, Lookahead), Production>,
+}
+
+impl Ll1Table {
+    pub fn new(grammar: &CFG) -> Result<Ll1Table, Vec<Conflict>> {
+        let first = grammar.first_sets();
+        let follow = grammar.follow_sets();
+
+        let mut cells: BTreeMap<(Nonterminal, Lookahead), Vec<Production>> = BTreeMap::new();
+        for rule in &grammar.productions {
+            let mut items = CFG::first_of(&first, &rule.right);
+            if items.remove(&Lookahead::Epsilon) {
+                items.extend(follow[&rule.left].iter().cloned());
+            }
+            for item in items {
+                cells
+                    .entry((rule.left.clone(), item))
+                    .or_insert_with(Vec::new)
+                    .push(rule.clone());
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        let mut table = HashMap::new();
+        for ((left, lookahead), mut productions) in cells {
+            if productions.len() > 1 {
+                conflicts.push(Conflict {
+                    left: left,
+                    lookahead: lookahead,
+                    productions: productions,
+                });
+            } else {
+                table.insert((left, lookahead), productions.remove(0));
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(Ll1Table { table: table })
+        } else {
+            Err(conflicts)
+        }
+    }
+
+    pub fn get(&self, left: &Nonterminal, lookahead: &Lookahead) -> Option<&Production> {
+        self.table.get(&(left.clone(), lookahead.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use self::super::*;
+    use cfg::{Symbol, Terminal};
+    use std::io::Cursor;
+
+    #[test]
+    fn ll1_table() {
+        let test_rules = "
+            E -> TX
+            X -> +TX |
+            T -> FY
+            Y -> *FY |
+            F -> (E) | a
+        ";
+        let cfg = CFG::load_from_reader(Cursor::new(test_rules)).unwrap();
+        let table = cfg.ll1_table().unwrap();
+        let nonterm = |x: &str| Nonterminal::new(x.to_string(), 0);
+        let term = |x: char| Lookahead::T(Terminal::new(x));
+
+        let rule = table.get(&nonterm("X"), &term('+')).unwrap();
+        assert_eq!(join(&rule.right, ""), "+TX");
+        let rule = table.get(&nonterm("X"), &Lookahead::End).unwrap();
+        assert!(rule.right.is_empty());
+        let rule = table.get(&nonterm("F"), &term('(')).unwrap();
+        assert_eq!(join(&rule.right, ""), "(E)");
+        assert!(table.get(&nonterm("F"), &term('+')).is_none());
+
+        let cfg = CFG::load_from_reader(Cursor::new("S -> aS | a")).unwrap();
+        let conflicts = cfg.ll1_table().unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].left, nonterm("S"));
+        assert_eq!(conflicts[0].lookahead, term('a'));
+        assert_eq!(
+            conflicts[0].productions,
+            vec![
+                Production::new(nonterm("S"), vec![Symbol::T(Terminal::new('a'))]),
+                Production::new(
+                    nonterm("S"),
+                    vec![Symbol::T(Terminal::new('a')), Symbol::N(nonterm("S"))]
+                ),
+            ]
+        );
+    }
+}
@@ -1,6 +1,9 @@
 #[macro_use]
 extern crate clap;
 extern crate itertools;
+extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 
 #[macro_use]
 extern crate serde_derive;
@@ -8,11 +11,15 @@ extern crate serde;
 extern crate serde_yaml;
 
 mod args;
+mod automata;
 mod cfg;
 mod cyk;
 mod dfa;
+mod ebnf;
 mod earley;
 mod generator;
+mod ll1;
+mod parse;
 mod pda;
 mod pdt;
 mod sdt;
@@ -21,7 +28,7 @@ use cfg::{Symbol, CFG};
 use cyk::CYKParser;
 use dfa::DFA;
 use earley::EarleyParser;
-use generator::{GeneratedItem, GeneratedSet, Generator};
+use generator::{GeneratedDerivation, GeneratedItem, GeneratedSet, Generator};
 use itertools::{join, Itertools};
 use pda::DPDADesign;
 use pdt::DPDTDesign;
@@ -79,20 +86,22 @@ fn main() {
             max = value_t_or_exit!(matches, "len-max", u32);
         }
         let left = !matches.is_present("right");
-        let gen = Generator::new(cfg, min, max, left);
+        let mut gen = Generator::new(cfg, min, max, left).unwrap_or_else(|e| {
+            eprintln!("ERROR: {}", e);
+            process::exit(1);
+        });
+        if !matches.is_present("all") {
+            gen = gen.unique();
+        }
         let mut output_stream = BufWriter::new(get_output_stream(matches.value_of("OUT")));
-        let mut visited = HashSet::new();
-        for seq in gen {
-            if !matches.is_present("all") {
-                if visited.contains(&seq) {
-                    continue;
-                } else {
-                    visited.insert(seq.clone());
-                }
+        if matches.is_present("derivation") {
+            for (_, trace) in gen.derivations() {
+                output_stream
+                    .write_fmt(format_args!("{}\n", GeneratedDerivation(&trace)))
+                    .unwrap();
             }
-            output_stream
-                .write_fmt(format_args!("{}\n", GeneratedItem(&seq)))
-                .unwrap();
+        } else {
+            gen.write_to(&mut output_stream).unwrap();
         }
 
     //
@@ -310,14 +319,22 @@ fn main() {
         output_stream
             .write_fmt(format_args!(
                 "Chomsky Normal Form\nG({{{}}}, {{{}}}, P, {}) where P:\n{}\n",
-                join(chomsky_cfg.get_terminals().iter().collect::<Vec<_>>(), ","),
-                join(chomsky_cfg.get_variables().iter().collect::<Vec<_>>(), ","),
+                join(chomsky_cfg.terminals().iter().collect::<Vec<_>>(), ","),
+                join(chomsky_cfg.nonterminals().iter().collect::<Vec<_>>(), ","),
                 chomsky_cfg.start,
                 chomsky_cfg,
             )).unwrap();
 
-        let chomsky_gen = Generator::new(chomsky_cfg, min, max, true);
-        let gen = Generator::new(cfg, min, max, true);
+        let (chomsky_gen, gen) = match (
+            Generator::new(chomsky_cfg, min, max, true),
+            Generator::new(cfg, min, max, true),
+        ) {
+            (Ok(chomsky_gen), Ok(gen)) => (chomsky_gen, gen),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("ERROR: {}", e);
+                process::exit(1);
+            }
+        };
 
         let normal_set: HashSet<Vec<Symbol>> = gen.collect();
         let chomsky_set: HashSet<Vec<Symbol>> = chomsky_gen.collect();
@@ -0,0 +1,308 @@
+use cfg;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseTree {
+    Leaf(cfg::Terminal),
+    Node(cfg::Nonterminal, Vec<ParseTree>),
+}
+
+impl ParseTree {
+    /// Builds the tree from the productions of a leftmost derivation
+    pub fn from_leftmost<'a, I>(path: &mut I) -> Option<ParseTree>
+    where
+        I: Iterator<Item = &'a cfg::Production>,
+    {
+        let rule = path.next()?;
+        let mut children = Vec::new();
+        for sym in &rule.right {
+            match sym {
+                &cfg::Symbol::T(ref t) => children.push(ParseTree::Leaf(t.clone())),
+                &cfg::Symbol::N(_) => children.push(ParseTree::from_leftmost(path)?),
+            }
+        }
+        Some(ParseTree::Node(rule.left.clone(), children))
+    }
+
+    /// Renders the tree with box-drawing branches, one node per line
+    pub fn render_ascii(&self) -> String {
+        let mut out = format!("{}\n", self.label());
+        self.render_children(&mut out, "");
+        out
+    }
+
+    fn label(&self) -> String {
+        match self {
+            &ParseTree::Leaf(ref t) => t.to_string(),
+            &ParseTree::Node(ref n, _) => n.to_string(),
+        }
+    }
+
+    fn render_children(&self, out: &mut String, prefix: &str) {
+        if let &ParseTree::Node(_, ref children) = self {
+            for (idx, child) in children.iter().enumerate() {
+                let last = idx + 1 == children.len();
+                let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+                out.push_str(&format!("{}{}{}\n", prefix, branch, child.label()));
+                child.render_children(out, &format!("{}{}", prefix, indent));
+            }
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        match self {
+            &ParseTree::Leaf(ref t) => write!(f, "{:width$}{}\n", "", t, width = depth * 2),
+            &ParseTree::Node(ref n, ref children) => {
+                write!(f, "{:width$}{}\n", "", n, width = depth * 2)?;
+                for child in children {
+                    child.fmt_indented(f, depth + 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// The search tried more productions than its budget allows,
+/// so it is not known whether the input is in the language
+#[derive(Debug, PartialEq)]
+pub struct BudgetExceeded;
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The derivation search budget is exceeded")
+    }
+}
+
+/// Top-down search of a leftmost derivation of the input over the grammar as is.
+/// A Nonterminal deriving the same span of the input as its ancestor is skipped,
+/// a shortest derivation never needs it, so the search terminates even with cycles
+pub struct LeftmostSearch<'a> {
+    rules: HashMap<&'a cfg::Nonterminal, Vec<&'a cfg::Production>>,
+    input: Vec<char>,
+    path: HashSet<(&'a cfg::Nonterminal, usize, usize)>,
+    found: HashMap<(&'a cfg::Nonterminal, usize, usize), Vec<&'a cfg::Production>>,
+    budget: Option<usize>,
+    steps: usize,
+}
+
+impl<'a> LeftmostSearch<'a> {
+    pub fn new(grammar: &'a cfg::CFG, input: &str) -> LeftmostSearch<'a> {
+        let mut rules = HashMap::new();
+        for rule in &grammar.productions {
+            rules.entry(&rule.left).or_insert_with(Vec::new).push(rule);
+        }
+        LeftmostSearch {
+            rules: rules,
+            input: input.chars().collect(),
+            path: HashSet::new(),
+            found: HashMap::new(),
+            budget: None,
+            steps: 0,
+        }
+    }
+
+    /// The bound on the number of the productions tried by the search, unlimited by default
+    pub fn with_budget(mut self, budget: usize) -> LeftmostSearch<'a> {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The productions of the leftmost derivation of the whole input from the Nonterminal
+    pub fn derive(&mut self, start: &'a cfg::Nonterminal) -> Option<Vec<cfg::Production>> {
+        let end = self.input.len();
+        self.derive_span(start, 0, end)
+            .map(|path| path.into_iter().cloned().collect())
+    }
+
+    /// Same as derive, but tells the input out of the language from the spent budget
+    pub fn try_derive(
+        &mut self,
+        start: &'a cfg::Nonterminal,
+    ) -> Result<Option<Vec<cfg::Production>>, BudgetExceeded> {
+        match self.derive(start) {
+            None if self.exceeded() => Err(BudgetExceeded),
+            result => Ok(result),
+        }
+    }
+
+    fn exceeded(&self) -> bool {
+        self.budget.map_or(false, |budget| self.steps > budget)
+    }
+
+    fn derive_span(
+        &mut self,
+        left: &'a cfg::Nonterminal,
+        from: usize,
+        to: usize,
+    ) -> Option<Vec<&'a cfg::Production>> {
+        let key = (left, from, to);
+        if let Some(path) = self.found.get(&key) {
+            return Some(path.clone());
+        }
+        if !self.path.insert(key) {
+            return None;
+        }
+        let rules = self.rules.get(left).cloned().unwrap_or_default();
+        let mut result = None;
+        for rule in rules {
+            self.steps += 1;
+            if self.exceeded() {
+                break;
+            }
+            if let Some(mut path) = self.derive_seq(&rule.right, from, to) {
+                path.insert(0, rule);
+                result = Some(path);
+                break;
+            }
+        }
+        self.path.remove(&key);
+        if let Some(ref path) = result {
+            self.found.insert(key, path.clone());
+        }
+        result
+    }
+
+    fn derive_seq(
+        &mut self,
+        seq: &'a [cfg::Symbol],
+        from: usize,
+        to: usize,
+    ) -> Option<Vec<&'a cfg::Production>> {
+        let terminals = seq.iter().filter(|x| x.is_terminal()).count();
+        if terminals > to - from {
+            return None;
+        }
+        match seq.first() {
+            None if from == to => Some(Vec::new()),
+            None => None,
+            Some(&cfg::Symbol::T(ref t)) => {
+                if from < to && t.is_a(self.input[from]) {
+                    self.derive_seq(&seq[1..], from + 1, to)
+                } else {
+                    None
+                }
+            }
+            Some(&cfg::Symbol::N(ref n)) => {
+                for mid in from..to - terminals + 1 {
+                    if let Some(mut path) = self.derive_span(n, from, mid) {
+                        if let Some(rest) = self.derive_seq(&seq[1..], mid, to) {
+                            path.extend(rest);
+                            return Some(path);
+                        }
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use self::super::*;
+    use cfg::CFG;
+    use itertools::join;
+    use std::io::Cursor;
+
+    #[test]
+    fn parse_cyk() {
+        let cfg = CFG::load_from_reader(Cursor::new("S -> aSb | ab")).unwrap();
+        let expected = format!(
+            "{}\n",
+            join(
+                vec![
+                    "<S1>",
+                    "  <a>",
+                    "    a",
+                    "  <S2>",
+                    "    S",
+                    "      <a>",
+                    "        a",
+                    "      <b>",
+                    "        b",
+                    "    <b>",
+                    "      b",
+                ],
+                "\n"
+            )
+        );
+        let tree = cfg.parse_cyk("aabb").unwrap();
+        assert_eq!(format!("{}", tree), expected);
+        assert!(cfg.parse_cyk("aab").is_none());
+    }
+
+    #[test]
+    fn render_ascii() {
+        let cfg = CFG::load_from_str("S -> aSb | ab | c").unwrap();
+        let path = cfg.derive("aacbb").unwrap();
+        let tree = ParseTree::from_leftmost(&mut path.iter()).unwrap();
+        let expected = join(
+            vec![
+                "S",
+                "├── a",
+                "├── S",
+                "│   ├── a",
+                "│   ├── S",
+                "│   │   └── c",
+                "│   └── b",
+                "└── b",
+                "",
+            ],
+            "\n",
+        );
+        assert_eq!(tree.render_ascii(), expected);
+    }
+
+    #[test]
+    fn derive() {
+        // replay the leftmost derivation
+        let replay = |cfg: &CFG, path: &Vec<cfg::Production>| {
+            let mut form = vec![cfg::Symbol::N(cfg.start.clone())];
+            for rule in path {
+                let idx = form.iter().position(|x| x.is_nonterminal()).unwrap();
+                assert!(form[idx].is_eq_nonterm(&rule.left));
+                form.splice(idx..idx + 1, rule.right.iter().cloned());
+            }
+            join(form, "")
+        };
+        let cfg = CFG::load_from_str("S -> aSb | ab").unwrap();
+        let path = cfg.derive("aabb").unwrap();
+        assert_eq!(replay(&cfg, &path), "aabb");
+        assert!(cfg.derive("aab").is_none());
+        assert!(cfg.derive("").is_none());
+
+        let test_rules = "
+            E -> E+T | T
+            T -> T*F | F
+            F -> (E) | a | A
+            A -> F | E |
+        ";
+        let cfg = CFG::load_from_str(test_rules).unwrap();
+        for input in vec!["a+a*(a)", "()", "a+", "+*"] {
+            let path = cfg.derive(input).unwrap();
+            assert_eq!(replay(&cfg, &path), input);
+        }
+        assert!(cfg.derive("a)").is_none());
+    }
+
+    #[test]
+    fn derive_with_budget() {
+        let cfg = CFG::load_from_str("S -> SS | aS | a").unwrap();
+        let input = "a".repeat(30);
+        assert_eq!(cfg.derive_with_budget(&input, 10), Err(BudgetExceeded));
+        assert_eq!(cfg.accepts_with_budget(&input, 10), Err(BudgetExceeded));
+        let path = cfg.derive_with_budget(&input, 10000).unwrap().unwrap();
+        // 'S -> SS' is tried first: 29 times 'S -> SS' and 30 times 'S -> a'
+        assert_eq!(path.len(), 59);
+        assert_eq!(cfg.accepts_with_budget("aab", 10000), Ok(false));
+        assert_eq!(cfg.accepts_with_budget("aaa", 10000), Ok(true));
+    }
+}
@@ -0,0 +1,38 @@
+extern crate assert_cmd;
+
+use assert_cmd::prelude::*;
+use std::process::Command;
+
+fn plt() -> Command {
+    Command::cargo_bin("plt").unwrap()
+}
+
+#[test]
+fn generate() {
+    // the empty string of the language is the first line
+    plt()
+        .args(&["gen", "--len-max", "4", "sample/cfg/brackets.txt"])
+        .assert()
+        .success()
+        .stdout("\n()\n()()\n(())\n");
+}
+
+#[test]
+fn simplify() {
+    plt()
+        .args(&["simplify", "sample/cfg/brackets.txt"])
+        .assert()
+        .success()
+        .stdout(
+            "<S1> ->  | () | ()S | (S) | (S)S | S() | S()S | S(S) | S(S)S\n\
+             S -> () | ()S | (S) | (S)S | S() | S()S | S(S) | S(S)S\n",
+        );
+}
+
+#[test]
+fn missing_file() {
+    plt()
+        .args(&["simplify", "sample/cfg/no-such-file.txt"])
+        .assert()
+        .failure();
+}